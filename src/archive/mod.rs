@@ -0,0 +1,252 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use chrono::DateTime;
+use chrono::Datelike;
+use chrono::Local;
+use chrono::Timelike;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use tokio::io::AsyncWrite;
+use tokio_util::io::SyncIoBridge;
+use zip::write::SimpleFileOptions;
+use zip::CompressionMethod;
+use zip::ZipWriter;
+
+use crate::config::Config;
+use crate::utils::query::parse_query;
+
+/// Formats a directory can be downloaded as with "?download="
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+  Zip,
+  TarGz,
+}
+
+impl ArchiveFormat {
+  pub fn from_query(query: Option<&str>) -> Option<Self> {
+    let (_, value) = parse_query(query)
+      .into_iter()
+      .find(|(key, _)| key == "download")?;
+
+    match value.as_str() {
+      "zip" => Some(ArchiveFormat::Zip),
+      "tar.gz" | "tgz" => Some(ArchiveFormat::TarGz),
+      _ => None,
+    }
+  }
+
+  pub fn extension(&self) -> &'static str {
+    match self {
+      ArchiveFormat::Zip => "zip",
+      ArchiveFormat::TarGz => "tar.gz",
+    }
+  }
+
+  pub fn content_type(&self) -> &'static str {
+    match self {
+      ArchiveFormat::Zip => "application/zip",
+      ArchiveFormat::TarGz => "application/gzip",
+    }
+  }
+}
+
+/// A file or directory to add to an archive
+struct ArchiveEntry {
+  path: PathBuf,
+  /// Path inside the archive, always "/" separated
+  name: String,
+  is_dir: bool,
+}
+
+/// Name of the top level folder in an archive of "dir"
+pub fn archive_name(
+  config: &Config,
+  dir: &Path,
+) -> String {
+  dir
+    .file_name()
+    .or_else(|| config.serve_dir_abs.file_name())
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_else(|| "archive".to_string())
+}
+
+/// Write an archive of "dir" into "writer" one file at a time, so the
+/// directory is never held in memory. Files the server would refuse to
+/// serve are left out. Archiving runs on the blocking thread pool
+pub async fn write_archive<W>(
+  config: Arc<Config>,
+  dir: PathBuf,
+  format: ArchiveFormat,
+  writer: W,
+) -> io::Result<()>
+where
+  W: AsyncWrite + Unpin + Send + 'static,
+{
+  let disconnected = Arc::new(AtomicBool::new(false));
+  let writer = ClientWriter {
+    inner: SyncIoBridge::new(writer),
+    disconnected: disconnected.clone(),
+  };
+
+  let result = tokio::task::spawn_blocking(move || -> io::Result<()> {
+    let root = archive_name(&config, &dir);
+    let mut entries = Vec::<ArchiveEntry>::new();
+    collect_entries(&config, &dir, &root, &mut entries)?;
+
+    let mut writer = match format {
+      ArchiveFormat::Zip => write_zip(writer, &entries)?,
+      ArchiveFormat::TarGz => write_tar_gz(writer, &entries)?,
+    };
+
+    writer.flush()?;
+    writer.inner.shutdown()
+  })
+  .await?;
+
+  // A client cancelling the download isn't an error
+  match result {
+    Err(_) if disconnected.load(Ordering::Relaxed) => Ok(()),
+    result => result,
+  }
+}
+
+/// The response body. Writes only fail once the client has gone away,
+/// after that everything is discarded so the archive writers can be
+/// dropped mid-archive without complaining
+struct ClientWriter<W: Write> {
+  inner: W,
+  disconnected: Arc<AtomicBool>,
+}
+
+impl<W: Write> Write for ClientWriter<W> {
+  fn write(
+    &mut self,
+    buf: &[u8],
+  ) -> io::Result<usize> {
+    if self.disconnected.load(Ordering::Relaxed) {
+      return Ok(buf.len());
+    }
+    self.inner.write(buf).inspect_err(|_| {
+      self.disconnected.store(true, Ordering::Relaxed);
+    })
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    if self.disconnected.load(Ordering::Relaxed) {
+      return Ok(());
+    }
+    self.inner.flush().inspect_err(|_| {
+      self.disconnected.store(true, Ordering::Relaxed);
+    })
+  }
+}
+
+/// Walk "dir" depth first in name order. Only paths are collected, file
+/// contents are read while writing. Symlinked directories aren't entered
+/// so links pointing up the tree can't recurse forever
+fn collect_entries(
+  config: &Config,
+  dir: &Path,
+  name: &str,
+  entries: &mut Vec<ArchiveEntry>,
+) -> io::Result<()> {
+  entries.push(ArchiveEntry {
+    path: dir.to_path_buf(),
+    name: format!("{}/", name),
+    is_dir: true,
+  });
+
+  let mut items = fs::read_dir(dir)?.collect::<io::Result<Vec<fs::DirEntry>>>()?;
+  items.sort_by_key(|item| item.file_name());
+
+  for item in items {
+    let path = item.path();
+    if config.is_hidden(&path) {
+      continue;
+    }
+
+    let item_name = format!("{}/{}", name, item.file_name().to_string_lossy());
+    let Ok(meta) = fs::metadata(&path) else {
+      // Broken symlinks
+      continue;
+    };
+
+    if meta.is_dir() {
+      if !item.file_type()?.is_symlink() {
+        collect_entries(config, &path, &item_name, entries)?;
+      }
+    } else {
+      entries.push(ArchiveEntry {
+        path,
+        name: item_name,
+        is_dir: false,
+      });
+    }
+  }
+
+  Ok(())
+}
+
+fn write_zip<W: Write>(
+  writer: W,
+  entries: &[ArchiveEntry],
+) -> io::Result<W> {
+  let mut zip = ZipWriter::new_stream(writer);
+
+  for entry in entries {
+    let meta = fs::metadata(&entry.path)?;
+    let mut options = SimpleFileOptions::default()
+      .compression_method(CompressionMethod::Deflated)
+      .large_file(meta.len() >= u32::MAX as u64);
+    if let Some(modified) = meta.modified().ok().and_then(zip_time) {
+      options = options.last_modified_time(modified);
+    }
+
+    if entry.is_dir {
+      zip.add_directory(&entry.name, options)?;
+    } else {
+      zip.start_file(&entry.name, options)?;
+      io::copy(&mut File::open(&entry.path)?, &mut zip)?;
+    }
+  }
+
+  Ok(zip.finish()?.into_inner())
+}
+
+fn write_tar_gz<W: Write>(
+  writer: W,
+  entries: &[ArchiveEntry],
+) -> io::Result<W> {
+  let mut tar = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+
+  for entry in entries {
+    match entry.is_dir {
+      true => tar.append_dir(&entry.name, &entry.path)?,
+      false => tar.append_file(&entry.name, &mut File::open(&entry.path)?)?,
+    }
+  }
+
+  tar.into_inner()?.finish()
+}
+
+/// Zip timestamps are stored in local time without a time zone
+fn zip_time(time: std::time::SystemTime) -> Option<zip::DateTime> {
+  let time: DateTime<Local> = time.into();
+  zip::DateTime::from_date_and_time(
+    time.year().try_into().ok()?,
+    time.month() as u8,
+    time.day() as u8,
+    time.hour() as u8,
+    time.minute() as u8,
+    time.second() as u8,
+  )
+  .ok()
+}
@@ -0,0 +1,87 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::config::Config;
+
+/// How request paths ending in "/" are treated
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrailingSlash {
+  /// Serve paths with or without a trailing slash
+  #[default]
+  Ignore,
+  /// Redirect directories (and clean URLs) to the path with a trailing slash
+  Add,
+  /// Redirect paths ending in a slash to the path without it
+  Remove,
+}
+
+/// Outcome of mapping a request onto the serve directory
+pub enum Resolved {
+  /// Serve this path, which may not exist
+  Path(PathBuf),
+  /// The request path isn't canonical, redirect to this path instead
+  Redirect(String),
+}
+
+/// The ".html" file a clean URL ("/about" or "/about/") maps to, if it exists
+pub fn html_path(file_path: &Path) -> Option<PathBuf> {
+  let file_name = file_path.file_name()?.to_str()?;
+  let html_path = file_path.with_file_name(format!("{}.html", file_name));
+  html_path.is_file().then_some(html_path)
+}
+
+/// Apply clean URLs and the trailing slash policy to a request for
+/// "url_path", which maps to "file_path" on disk. Requests rewritten by a
+/// rule are never redirected as the client didn't ask for "url_path"
+pub fn resolve(
+  config: &Config,
+  url_path: &str,
+  mut file_path: PathBuf,
+  is_rewrite: bool,
+) -> Resolved {
+  let mut canonical = url_path.to_string();
+  let mut is_clean = false;
+
+  if config.clean_urls {
+    if !file_path.exists() {
+      if let Some(html_path) = html_path(&file_path) {
+        file_path = html_path;
+        is_clean = true;
+      }
+    } else if config.clean_urls_redirect && file_path.is_file() && url_path.ends_with(".html") {
+      // "/about.html" becomes "/about" and "/docs/index.html" becomes "/docs/"
+      let index = config
+        .index
+        .iter()
+        .find(|index| canonical.ends_with(&format!("/{}", index)));
+      match index {
+        Some(index) => canonical.truncate(canonical.len() - index.len()),
+        None => canonical = canonical.trim_end_matches(".html").to_string(),
+      }
+      is_clean = true;
+    }
+  }
+
+  match config.trailing_slash {
+    TrailingSlash::Add if !canonical.ends_with('/') && (is_clean || file_path.is_dir()) => {
+      canonical.push('/');
+    }
+    TrailingSlash::Remove if canonical.ends_with('/') && canonical != "/" => {
+      canonical = canonical.trim_end_matches('/').to_string();
+      if canonical.is_empty() {
+        canonical = "/".to_string();
+      }
+    }
+    _ => {}
+  }
+
+  if !is_rewrite && canonical != url_path {
+    return Resolved::Redirect(canonical);
+  }
+
+  Resolved::Path(file_path)
+}
@@ -1,64 +1,368 @@
 use std::path::PathBuf;
 
+use clap::builder::FalseyValueParser;
+use clap::value_parser;
+use clap::Args;
 use clap::Parser;
+use clap::Subcommand;
+
+use crate::clean_urls::TrailingSlash;
+use crate::utils::size::SizeUnits;
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, disable_help_subcommand = true)]
 pub struct CliCommand {
+  #[command(subcommand)]
+  pub subcommand: Option<CliSubcommand>,
+
   /// Target directory to serve
-  #[arg(default_value = "./dist")]
+  #[arg(default_value = "./dist", env = "HTTP_SERVER_SERVE_DIR")]
   pub serve_dir: PathBuf,
 
-  #[arg(short = 'a', long = "address", default_value = "0.0.0.0")]
+  /// Load options from a config file [default: http-server.toml or http-server.json in the cwd or SERVE_DIR]
+  #[arg(long = "config", env = "HTTP_SERVER_CONFIG")]
+  pub config: Option<PathBuf>,
+
+  #[arg(
+    short = 'a',
+    long = "address",
+    default_value = "0.0.0.0",
+    env = "HTTP_SERVER_ADDRESS"
+  )]
   pub address: String,
 
-  #[arg(short = 'p', long = "port", default_value = "8080")]
+  #[arg(
+    short = 'p',
+    long = "port",
+    default_value = "8080",
+    env = "HTTP_SERVER_PORT"
+  )]
   pub port: usize,
 
+  /// Serve over HTTPS using this PEM certificate chain (requires --tls-key)
+  #[arg(long = "tls-cert", env = "HTTP_SERVER_TLS_CERT")]
+  pub tls_cert: Option<PathBuf>,
+
+  /// PEM private key for the certificate passed to --tls-cert
+  #[arg(long = "tls-key", env = "HTTP_SERVER_TLS_KEY")]
+  pub tls_key: Option<PathBuf>,
+
+  /// Serve over HTTPS using a self-signed certificate generated at startup
+  #[arg(
+    long = "tls-self-signed",
+    env = "HTTP_SERVER_TLS_SELF_SIGNED",
+    value_parser = FalseyValueParser::new()
+  )]
+  pub tls_self_signed: bool,
+
   /// Redirect requests to /index.html for Single Page Applications
-  #[arg(long = "spa")]
+  #[arg(
+    long = "spa",
+    env = "HTTP_SERVER_SPA",
+    value_parser = FalseyValueParser::new()
+  )]
   pub spa: bool,
 
+  /// File names served for a directory, in order of preference
+  #[arg(
+    long = "index",
+    default_value = "index.html",
+    env = "HTTP_SERVER_INDEX",
+    value_delimiter = ','
+  )]
+  pub index: Vec<String>,
+
+  /// Don't list the contents of directories without an index file
+  #[arg(
+    long = "no-dir-listing",
+    env = "HTTP_SERVER_NO_DIR_LISTING",
+    value_parser = FalseyValueParser::new()
+  )]
+  pub no_dir_listing: bool,
+
+  /// List and serve dotfiles such as ".env" (".well-known" is always served)
+  #[arg(
+    long = "show-hidden",
+    env = "HTTP_SERVER_SHOW_HIDDEN",
+    value_parser = FalseyValueParser::new()
+  )]
+  pub show_hidden: bool,
+
+  /// Don't list or serve paths matching a glob (.gitignore syntax, relative to the serve directory)
+  #[arg(long = "ignore", env = "HTTP_SERVER_IGNORE", value_delimiter = '\n')]
+  pub ignore: Vec<String>,
+
+  /// Handlebars template used to render directory listings
+  #[arg(long = "dir-template", env = "HTTP_SERVER_DIR_TEMPLATE")]
+  pub dir_template: Option<PathBuf>,
+
+  /// Serve Markdown files as HTML, "?raw" returns the original text
+  #[arg(
+    long = "render-markdown",
+    env = "HTTP_SERVER_RENDER_MARKDOWN",
+    value_parser = FalseyValueParser::new()
+  )]
+  pub render_markdown: bool,
+
+  /// Units for file sizes in directory listings
+  #[arg(
+    long = "size-units",
+    default_value = "si",
+    env = "HTTP_SERVER_SIZE_UNITS",
+    value_enum
+  )]
+  pub size_units: SizeUnits,
+
+  /// Serve "about.html" for "/about"
+  #[arg(
+    long = "clean-urls",
+    env = "HTTP_SERVER_CLEAN_URLS",
+    value_parser = FalseyValueParser::new()
+  )]
+  pub clean_urls: bool,
+
+  /// Redirect "/about.html" to "/about" (implies --clean-urls)
+  #[arg(
+    long = "clean-urls-redirect",
+    env = "HTTP_SERVER_CLEAN_URLS_REDIRECT",
+    value_parser = FalseyValueParser::new()
+  )]
+  pub clean_urls_redirect: bool,
+
+  /// Redirect to add or remove the trailing slash of request paths
+  #[arg(
+    long = "trailing-slash",
+    default_value = "ignore",
+    env = "HTTP_SERVER_TRAILING_SLASH",
+    value_enum
+  )]
+  pub trailing_slash: TrailingSlash,
+
   /// Cache control time
-  #[arg(short = 'c', long = "cache-time", default_value = "0")]
+  #[arg(
+    short = 'c',
+    long = "cache-time",
+    default_value = "0",
+    env = "HTTP_SERVER_CACHE_TIME"
+  )]
   pub cache_time: usize,
 
+  /// Derive ETags from a hash of the file contents instead of size and modification time
+  #[arg(
+    long = "etag-hash",
+    env = "HTTP_SERVER_ETAG_HASH",
+    value_parser = FalseyValueParser::new()
+  )]
+  pub etag_hash: bool,
+
   /// Compress responses (JIT)
-  #[arg(short = 'Z', long = "compress")]
+  #[arg(
+    short = 'Z',
+    long = "compress",
+    env = "HTTP_SERVER_COMPRESS",
+    value_parser = FalseyValueParser::new()
+  )]
   pub compress: bool,
 
+  /// Memory budget in megabytes for caching compressed responses (0 to disable)
+  #[arg(
+    long = "compress-cache-size",
+    default_value = "64",
+    env = "HTTP_SERVER_COMPRESS_CACHE_SIZE"
+  )]
+  pub compress_cache_size: usize,
+
+  /// Brotli quality used when compressing (0-11)
+  #[arg(
+    long = "brotli-level",
+    default_value = "11",
+    env = "HTTP_SERVER_BROTLI_LEVEL",
+    value_parser = value_parser!(u32).range(0..=11)
+  )]
+  pub brotli_level: u32,
+
+  /// Gzip and deflate level used when compressing (0-9)
+  #[arg(
+    long = "gzip-level",
+    default_value = "6",
+    env = "HTTP_SERVER_GZIP_LEVEL",
+    value_parser = value_parser!(u32).range(0..=9)
+  )]
+  pub gzip_level: u32,
+
+  /// Zstandard level used when compressing (1-22)
+  #[arg(
+    long = "zstd-level",
+    default_value = "3",
+    env = "HTTP_SERVER_ZSTD_LEVEL",
+    value_parser = value_parser!(i32).range(1..=22)
+  )]
+  pub zstd_level: i32,
+
   /// Custom headers (Format "key:value")
-  #[arg(short = 'H', long = "header")]
+  #[arg(
+    short = 'H',
+    long = "header",
+    env = "HTTP_SERVER_HEADERS",
+    value_delimiter = '\n'
+  )]
   pub headers: Vec<String>,
 
   /// Put server behind basic auth (Format "username:password")
-  #[arg(long = "auth")]
+  #[arg(long = "auth", env = "HTTP_SERVER_AUTH", value_delimiter = '\n')]
   pub basic_auth: Vec<String>,
 
+  /// Forward requests under a path to another server (Format "/path=http://host:port")
+  #[arg(long = "proxy", env = "HTTP_SERVER_PROXY", value_delimiter = '\n')]
+  pub proxy: Vec<String>,
+
   /// Enable CORS header
-  #[arg(long = "cors")]
+  #[arg(
+    long = "cors",
+    env = "HTTP_SERVER_CORS",
+    value_parser = FalseyValueParser::new()
+  )]
   pub cors: bool,
 
+  /// Origins allowed to make cross-origin requests, globs are supported (implies --cors) [default: *]
+  #[arg(
+    long = "cors-origin",
+    env = "HTTP_SERVER_CORS_ORIGIN",
+    value_delimiter = ','
+  )]
+  pub cors_origin: Vec<String>,
+
+  /// Allow credentialed cross-origin requests (cookies, auth headers), requires --cors-origin
+  #[arg(
+    long = "cors-credentials",
+    env = "HTTP_SERVER_CORS_CREDENTIALS",
+    value_parser = FalseyValueParser::new()
+  )]
+  pub cors_credentials: bool,
+
+  /// Seconds browsers may cache a CORS preflight response
+  #[arg(
+    long = "cors-max-age",
+    default_value = "86400",
+    env = "HTTP_SERVER_CORS_MAX_AGE"
+  )]
+  pub cors_max_age: u64,
+
   /// Enable headers for SharedArrayBuffer
-  #[arg(short = 'S', long = "shared-array-buffer")]
+  #[arg(
+    short = 'S',
+    long = "shared-array-buffer",
+    env = "HTTP_SERVER_SHARED_ARRAY_BUFFER",
+    value_parser = FalseyValueParser::new()
+  )]
   pub sab: bool,
 
   /// Don't print any logs to terminal
-  #[arg(short = 'Q', long = "quiet")]
+  #[arg(
+    short = 'Q',
+    long = "quiet",
+    env = "HTTP_SERVER_QUIET",
+    value_parser = FalseyValueParser::new()
+  )]
   pub quiet: bool,
 
   /// Watch folder for changes and trigger a browser reload
-  #[arg(short = 'w', long = "watch")]
+  #[arg(
+    short = 'w',
+    long = "watch",
+    env = "HTTP_SERVER_WATCH",
+    value_parser = FalseyValueParser::new()
+  )]
   pub watch: bool,
 
   /// Watch for changes [default: SERVE_DIR]
-  #[arg(long = "watch-dir")]
+  #[arg(long = "watch-dir", env = "HTTP_SERVER_WATCH_DIR")]
   pub watch_dir: Option<PathBuf>,
 
   /// Don't automatically inject watch listener into html
-  #[arg(long = "no-watch-inject")]
+  #[arg(
+    long = "no-watch-inject",
+    env = "HTTP_SERVER_NO_WATCH_INJECT",
+    value_parser = FalseyValueParser::new()
+  )]
   pub no_watch_inject: bool,
 
   /// Configure the buffer size when streaming files
-  #[arg(long = "stream-buffer-size", default_value = "4000")]
+  #[arg(
+    long = "stream-buffer-size",
+    default_value = "4000",
+    env = "HTTP_SERVER_STREAM_BUFFER_SIZE"
+  )]
   pub stream_buffer_size: usize,
+
+  /// Seconds to wait for in-flight requests to finish when shutting down
+  #[arg(
+    long = "shutdown-timeout",
+    default_value = "10",
+    env = "HTTP_SERVER_SHUTDOWN_TIMEOUT"
+  )]
+  pub shutdown_timeout: u64,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CliSubcommand {
+  /// Write .br, .zst and .gz files next to compressible files in a directory
+  Precompress(PrecompressCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct PrecompressCommand {
+  /// Directory to precompress
+  #[arg(default_value = "./dist")]
+  pub dir: PathBuf,
+
+  /// Encodings to generate
+  #[arg(
+    short = 'e',
+    long = "encoding",
+    value_delimiter = ',',
+    default_value = "br,zstd,gzip",
+    value_parser = ["br", "zstd", "gzip"]
+  )]
+  pub encodings: Vec<String>,
+
+  /// Skip files smaller than this many bytes
+  #[arg(long = "min-size", default_value = "1024")]
+  pub min_size: u64,
+
+  /// Skip writing a file unless it saves at least this percentage of the original size
+  #[arg(
+    long = "min-savings",
+    default_value = "10",
+    value_parser = value_parser!(u8).range(0..=100)
+  )]
+  pub min_savings: u8,
+
+  /// Regenerate files even when they are newer than the original
+  #[arg(short = 'f', long = "force")]
+  pub force: bool,
+
+  /// Brotli quality (0-11)
+  #[arg(
+    long = "brotli-level",
+    default_value = "11",
+    value_parser = value_parser!(u32).range(0..=11)
+  )]
+  pub brotli_level: u32,
+
+  /// Gzip level (0-9)
+  #[arg(
+    long = "gzip-level",
+    default_value = "9",
+    value_parser = value_parser!(u32).range(0..=9)
+  )]
+  pub gzip_level: u32,
+
+  /// Zstandard level (1-22)
+  #[arg(
+    long = "zstd-level",
+    default_value = "19",
+    value_parser = value_parser!(i32).range(1..=22)
+  )]
+  pub zstd_level: i32,
 }
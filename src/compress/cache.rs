@@ -0,0 +1,95 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use lru::LruCache;
+
+use super::Encoding;
+
+/// Identifies a compressed response. The size and modification time of the
+/// source file are part of the key so stale entries are never served
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CompressCacheKey {
+  pub path: PathBuf,
+  pub encoding: Encoding,
+  pub modified: i64,
+  pub content_length: u64,
+}
+
+/// LRU cache of compressed responses bounded by the total size of the
+/// cached bodies
+pub struct CompressCache {
+  budget: usize,
+  inner: Mutex<CompressCacheInner>,
+}
+
+struct CompressCacheInner {
+  entries: LruCache<CompressCacheKey, Arc<Vec<u8>>>,
+  size: usize,
+}
+
+impl CompressCache {
+  /// Create a cache holding at most "budget" bytes, a budget of 0 disables caching
+  pub fn new(budget: usize) -> Self {
+    Self {
+      budget,
+      inner: Mutex::new(CompressCacheInner {
+        entries: LruCache::unbounded(),
+        size: 0,
+      }),
+    }
+  }
+
+  pub fn get(
+    &self,
+    key: &CompressCacheKey,
+  ) -> Option<Arc<Vec<u8>>> {
+    let mut inner = self.inner.lock().unwrap();
+    inner.entries.get(key).cloned()
+  }
+
+  pub fn insert(
+    &self,
+    key: CompressCacheKey,
+    value: Arc<Vec<u8>>,
+  ) {
+    if value.len() > self.budget {
+      return;
+    }
+
+    let mut inner = self.inner.lock().unwrap();
+    inner.size += value.len();
+    if let Some((_, replaced)) = inner.entries.push(key, value) {
+      inner.size -= replaced.len();
+    }
+
+    while inner.size > self.budget {
+      let Some((_, evicted)) = inner.entries.pop_lru() else {
+        break;
+      };
+      inner.size -= evicted.len();
+    }
+  }
+
+  /// Drop every entry for a changed path, or anything beneath it if it's a directory
+  pub fn invalidate(
+    &self,
+    changed: &Path,
+  ) {
+    let mut inner = self.inner.lock().unwrap();
+
+    let stale = inner
+      .entries
+      .iter()
+      .filter(|(key, _)| key.path.starts_with(changed))
+      .map(|(key, _)| key.clone())
+      .collect::<Vec<CompressCacheKey>>();
+
+    for key in stale {
+      if let Some(removed) = inner.entries.pop(&key) {
+        inner.size -= removed.len();
+      }
+    }
+  }
+}
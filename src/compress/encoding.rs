@@ -0,0 +1,51 @@
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+/// Content codings the server can produce, either JIT or from sidecar files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+  Brotli,
+  Zstd,
+  Gzip,
+  Deflate,
+}
+
+impl Encoding {
+  /// All encodings in server preference order
+  pub const ALL: [Encoding; 4] = [
+    Encoding::Brotli,
+    Encoding::Zstd,
+    Encoding::Gzip,
+    Encoding::Deflate,
+  ];
+
+  /// Token used in the "Accept-Encoding" and "Content-Encoding" headers
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Encoding::Brotli => "br",
+      Encoding::Zstd => "zstd",
+      Encoding::Gzip => "gzip",
+      Encoding::Deflate => "deflate",
+    }
+  }
+
+  /// File extension of a precompressed sidecar file
+  pub fn extension(&self) -> Option<&'static str> {
+    match self {
+      Encoding::Brotli => Some("br"),
+      Encoding::Zstd => Some("zst"),
+      Encoding::Gzip => Some("gz"),
+      Encoding::Deflate => None,
+    }
+  }
+}
+
+impl Display for Encoding {
+  fn fmt(
+    &self,
+    f: &mut Formatter<'_>,
+  ) -> fmt::Result {
+    write!(f, "{}", self.as_str())
+  }
+}
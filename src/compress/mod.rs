@@ -1,9 +1,111 @@
+mod cache;
+mod encoding;
+mod negotiate;
+mod stream;
+
 use std::io::Write;
 
 use brotli as brotli_rs;
+use flate2::write::GzEncoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use zstd as zstd_rs;
+
+pub use self::cache::*;
+pub use self::encoding::*;
+pub use self::negotiate::*;
+pub use self::stream::*;
+
+/// Quality level used for each compression algorithm
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionLevels {
+  /// 0-11
+  pub brotli: u32,
+  /// 0-9, used for both gzip and deflate
+  pub gzip: u32,
+  /// 1-22
+  pub zstd: i32,
+}
+
+impl Default for CompressionLevels {
+  fn default() -> Self {
+    Self {
+      brotli: 11,
+      gzip: 6,
+      zstd: 3,
+    }
+  }
+}
+
+/// Whether a MIME type benefits from compression. Images, video, audio
+/// and archives are already compressed so are sent as they are
+pub fn is_compressible(mime: &str) -> bool {
+  let mime = mime.split(';').next().unwrap_or("").trim();
 
-pub fn brotli(input: &[u8]) -> Vec<u8> {
-  let mut writer = brotli_rs::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+  mime.starts_with("text/")
+    || mime.ends_with("+json")
+    || mime.ends_with("+xml")
+    || matches!(
+      mime,
+      "application/javascript"
+        | "application/x-javascript"
+        | "application/json"
+        | "application/xml"
+        | "application/wasm"
+        | "application/x-sh"
+        | "application/vnd.ms-fontobject"
+        | "font/ttf"
+        | "font/otf"
+        | "image/bmp"
+        | "image/x-icon"
+        | "image/vnd.microsoft.icon"
+    )
+}
+
+pub fn compress(
+  input: &[u8],
+  encoding: Encoding,
+  levels: &CompressionLevels,
+) -> Vec<u8> {
+  match encoding {
+    Encoding::Brotli => brotli(input, levels.brotli),
+    Encoding::Zstd => zstd(input, levels.zstd),
+    Encoding::Gzip => gzip(input, levels.gzip),
+    Encoding::Deflate => deflate(input, levels.gzip),
+  }
+}
+
+pub fn brotli(
+  input: &[u8],
+  quality: u32,
+) -> Vec<u8> {
+  let mut writer = brotli_rs::CompressorWriter::new(Vec::new(), 4096, quality, 22);
   writer.write_all(input).unwrap();
   writer.into_inner()
 }
+
+pub fn gzip(
+  input: &[u8],
+  level: u32,
+) -> Vec<u8> {
+  let mut writer = GzEncoder::new(Vec::new(), Compression::new(level));
+  writer.write_all(input).unwrap();
+  writer.finish().unwrap()
+}
+
+/// The "deflate" content coding is the zlib format (RFC 1950)
+pub fn deflate(
+  input: &[u8],
+  level: u32,
+) -> Vec<u8> {
+  let mut writer = ZlibEncoder::new(Vec::new(), Compression::new(level));
+  writer.write_all(input).unwrap();
+  writer.finish().unwrap()
+}
+
+pub fn zstd(
+  input: &[u8],
+  level: i32,
+) -> Vec<u8> {
+  zstd_rs::bulk::compress(input, level).unwrap()
+}
@@ -0,0 +1,137 @@
+use super::Encoding;
+
+/// Pick the best encoding from "available" (in server preference order) for
+/// an "Accept-Encoding" header. Returns None when the response should be sent
+/// without a content coding
+pub fn negotiate(
+  accept_encoding: Option<&str>,
+  available: &[Encoding],
+) -> Option<Encoding> {
+  let accept_encoding = accept_encoding?;
+
+  let mut wildcard = None::<f32>;
+  let mut explicit = Vec::<(String, f32)>::new();
+
+  for item in accept_encoding.split(',') {
+    let mut params = item.split(';');
+    let coding = params.next().unwrap_or("").trim().to_lowercase();
+    if coding.is_empty() {
+      continue;
+    }
+
+    let mut quality = 1.0;
+    for param in params {
+      let Some((key, value)) = param.split_once('=') else {
+        continue;
+      };
+      if key.trim().eq_ignore_ascii_case("q") {
+        quality = value.trim().parse::<f32>().unwrap_or(0.0).clamp(0.0, 1.0);
+      }
+    }
+
+    match coding.as_str() {
+      "*" => wildcard = Some(quality),
+      // Legacy alias for gzip
+      "x-gzip" => explicit.push(("gzip".to_string(), quality)),
+      _ => explicit.push((coding, quality)),
+    }
+  }
+
+  let quality_of = |encoding: &Encoding| -> f32 {
+    match explicit
+      .iter()
+      .find(|(coding, _)| coding == encoding.as_str())
+    {
+      Some((_, quality)) => *quality,
+      None => wildcard.unwrap_or(0.0),
+    }
+  };
+
+  let mut best = None::<(Encoding, f32)>;
+  for encoding in available {
+    let quality = quality_of(encoding);
+    if quality <= 0.0 {
+      continue;
+    }
+    // Ties are broken by server preference, which is the order of "available"
+    if best.is_none_or(|(_, best_quality)| quality > best_quality) {
+      best = Some((*encoding, quality));
+    }
+  }
+
+  let (encoding, quality) = best?;
+
+  // Prefer identity when the client explicitly ranks it higher
+  if explicit
+    .iter()
+    .any(|(coding, identity)| coding == "identity" && *identity > quality)
+  {
+    return None;
+  }
+
+  Some(encoding)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const AVAILABLE: [Encoding; 2] = [Encoding::Brotli, Encoding::Gzip];
+
+  fn pick(accept_encoding: &str) -> Option<Encoding> {
+    negotiate(Some(accept_encoding), &AVAILABLE)
+  }
+
+  #[test]
+  fn no_header_means_no_encoding() {
+    assert_eq!(negotiate(None, &AVAILABLE), None);
+    assert_eq!(pick(""), None);
+  }
+
+  #[test]
+  fn server_preference_breaks_ties() {
+    assert_eq!(pick("gzip, br"), Some(Encoding::Brotli));
+    assert_eq!(pick("gzip, deflate"), Some(Encoding::Gzip));
+  }
+
+  #[test]
+  fn quality_values_are_respected() {
+    assert_eq!(pick("br;q=0.5, gzip;q=0.8"), Some(Encoding::Gzip));
+    assert_eq!(pick("br;q=0, gzip"), Some(Encoding::Gzip));
+    assert_eq!(pick("gzip;q=0"), None);
+    assert_eq!(pick("GZIP; Q=0.9"), Some(Encoding::Gzip));
+  }
+
+  #[test]
+  fn wildcard_covers_unlisted_encodings() {
+    assert_eq!(pick("*"), Some(Encoding::Brotli));
+    assert_eq!(pick("br;q=0, *;q=0.5"), Some(Encoding::Gzip));
+    assert_eq!(pick("*;q=0"), None);
+  }
+
+  #[test]
+  fn x_gzip_is_gzip() {
+    assert_eq!(pick("x-gzip"), Some(Encoding::Gzip));
+  }
+
+  #[test]
+  fn identity_ranked_higher_wins() {
+    assert_eq!(pick("identity, gzip;q=0.5"), None);
+    assert_eq!(pick("identity;q=0.5, gzip"), Some(Encoding::Gzip));
+  }
+
+  #[test]
+  fn only_available_encodings_are_picked() {
+    assert_eq!(negotiate(Some("br"), &[Encoding::Gzip]), None);
+    assert_eq!(
+      negotiate(Some("br, gzip"), &[Encoding::Gzip]),
+      Some(Encoding::Gzip)
+    );
+  }
+
+  #[test]
+  fn unknown_codings_are_ignored() {
+    assert_eq!(pick("compress, sdch"), None);
+    assert_eq!(pick("invalid;q=abc, gzip"), Some(Encoding::Gzip));
+  }
+}
@@ -0,0 +1,60 @@
+use std::io;
+use std::io::Write;
+
+use brotli as brotli_rs;
+use flate2::write::GzEncoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio_util::io::SyncIoBridge;
+use zstd as zstd_rs;
+
+use super::CompressionLevels;
+use super::Encoding;
+
+/// Compress "reader" into "writer" chunk by chunk without buffering the
+/// whole input. Compression runs on the blocking thread pool so slow
+/// encoders don't stall the runtime
+pub async fn compress_stream<R, W>(
+  reader: R,
+  writer: W,
+  encoding: Encoding,
+  levels: CompressionLevels,
+) -> io::Result<()>
+where
+  R: AsyncRead + Unpin + Send + 'static,
+  W: AsyncWrite + Unpin + Send + 'static,
+{
+  let mut reader = SyncIoBridge::new(reader);
+  let writer = SyncIoBridge::new(writer);
+
+  tokio::task::spawn_blocking(move || -> io::Result<()> {
+    let mut writer = match encoding {
+      Encoding::Brotli => {
+        let mut encoder = brotli_rs::CompressorWriter::new(writer, 4096, levels.brotli, 22);
+        io::copy(&mut reader, &mut encoder)?;
+        encoder.into_inner()
+      }
+      Encoding::Zstd => {
+        let mut encoder = zstd_rs::stream::write::Encoder::new(writer, levels.zstd)?;
+        io::copy(&mut reader, &mut encoder)?;
+        encoder.finish()?
+      }
+      Encoding::Gzip => {
+        let mut encoder = GzEncoder::new(writer, Compression::new(levels.gzip));
+        io::copy(&mut reader, &mut encoder)?;
+        encoder.finish()?
+      }
+      Encoding::Deflate => {
+        let mut encoder = ZlibEncoder::new(writer, Compression::new(levels.gzip));
+        io::copy(&mut reader, &mut encoder)?;
+        encoder.finish()?
+      }
+    };
+
+    writer.flush()?;
+    writer.shutdown()
+  })
+  .await?
+}
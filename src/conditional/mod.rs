@@ -0,0 +1,242 @@
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::DateTime;
+use chrono::Utc;
+use http::HeaderMap;
+use lru::LruCache;
+use sha2::Digest;
+use sha2::Sha256;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+/// Weak ETag derived from the size and modification time of a file
+pub fn etag_from_metadata(
+  content_length: u64,
+  last_modified: &DateTime<Utc>,
+) -> String {
+  format!(
+    "W/\"{:x}-{:x}\"",
+    content_length,
+    last_modified.timestamp_millis()
+  )
+}
+
+/// Strong ETag derived from a hash of the contents of a file
+pub async fn etag_from_contents(file_path: &Path) -> anyhow::Result<String> {
+  let mut file = File::open(file_path).await?;
+  let mut hasher = Sha256::new();
+  let mut buf = vec![0; 64 * 1024];
+
+  loop {
+    let read = file.read(&mut buf).await?;
+    if read == 0 {
+      break;
+    }
+    hasher.update(&buf[..read]);
+  }
+
+  let hash = format!("{:x}", hasher.finalize());
+  Ok(format!("\"{}\"", &hash[..32]))
+}
+
+/// Content hashes for --etag-hash keyed by path, size and modification
+/// time, so each version of a file is read once rather than on every
+/// request (including 304 checks and byte ranges)
+pub struct EtagCache {
+  entries: Mutex<LruCache<(PathBuf, u64, i64), String>>,
+}
+
+impl EtagCache {
+  /// Create a cache remembering the hashes of at most "capacity" files
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      entries: Mutex::new(LruCache::new(
+        NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN),
+      )),
+    }
+  }
+
+  /// Strong ETag for a file, hashing it only when it changed
+  pub async fn etag_from_contents(
+    &self,
+    file_path: &Path,
+    content_length: u64,
+    last_modified: &DateTime<Utc>,
+  ) -> anyhow::Result<String> {
+    let key = (
+      file_path.to_path_buf(),
+      content_length,
+      last_modified.timestamp_millis(),
+    );
+    if let Some(etag) = self.entries.lock().unwrap().get(&key) {
+      return Ok(etag.clone());
+    }
+
+    let etag = etag_from_contents(file_path).await?;
+    self.entries.lock().unwrap().put(key, etag.clone());
+    Ok(etag)
+  }
+}
+
+/// Turn a strong ETag into a weak one, used when the response body is
+/// transformed (compressed or injected) and no longer byte-for-byte the file
+pub fn weaken(etag: &str) -> String {
+  match etag.starts_with("W/") {
+    true => etag.to_string(),
+    false => format!("W/{}", etag),
+  }
+}
+
+pub fn is_weak(etag: &str) -> bool {
+  etag.starts_with("W/")
+}
+
+/// Format a date for the "Last-Modified" header
+pub fn http_date(date: &DateTime<Utc>) -> String {
+  date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Parse a date from the "If-Modified-Since" or "If-Range" headers
+pub fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+  DateTime::parse_from_rfc2822(value.trim())
+    .ok()
+    .map(|date| date.with_timezone(&Utc))
+}
+
+/// Evaluate "If-None-Match" and "If-Modified-Since" against the current
+/// validators. Returns true when a 304 Not Modified should be sent
+pub fn is_not_modified(
+  headers: &HeaderMap,
+  etag: &str,
+  last_modified: &DateTime<Utc>,
+) -> bool {
+  // If-None-Match takes precedence and uses the weak comparison
+  if let Some(if_none_match) = headers.get("if-none-match") {
+    let Ok(if_none_match) = if_none_match.to_str() else {
+      return false;
+    };
+
+    return if_none_match.split(',').any(|tag| {
+      let tag = tag.trim();
+      tag == "*" || tag.trim_start_matches("W/") == etag.trim_start_matches("W/")
+    });
+  }
+
+  if let Some(if_modified_since) = headers.get("if-modified-since") {
+    let Some(since) = if_modified_since.to_str().ok().and_then(parse_http_date) else {
+      return false;
+    };
+
+    return last_modified.timestamp() <= since.timestamp();
+  }
+
+  false
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::TimeZone;
+
+  use super::*;
+
+  const ETAG: &str = "\"abc\"";
+
+  fn modified() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+  }
+
+  fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+      headers.insert(*name, value.parse().unwrap());
+    }
+    headers
+  }
+
+  fn not_modified(pairs: &[(&'static str, &'static str)]) -> bool {
+    is_not_modified(&headers(pairs), ETAG, &modified())
+  }
+
+  #[test]
+  fn no_validators() {
+    assert!(!not_modified(&[]));
+  }
+
+  #[test]
+  fn if_none_match_lists() {
+    assert!(not_modified(&[("if-none-match", "\"abc\"")]));
+    assert!(not_modified(&[("if-none-match", "\"x\", \"abc\"")]));
+    assert!(not_modified(&[("if-none-match", "\"x\",\"abc\" ")]));
+    assert!(!not_modified(&[("if-none-match", "\"x\", \"y\"")]));
+  }
+
+  #[test]
+  fn if_none_match_star() {
+    assert!(not_modified(&[("if-none-match", "*")]));
+  }
+
+  #[test]
+  fn if_none_match_uses_weak_comparison() {
+    assert!(not_modified(&[("if-none-match", "W/\"abc\"")]));
+    assert!(is_not_modified(
+      &headers(&[("if-none-match", "\"abc\"")]),
+      "W/\"abc\"",
+      &modified()
+    ));
+    assert!(!not_modified(&[("if-none-match", "W/\"abd\"")]));
+  }
+
+  #[test]
+  fn if_modified_since() {
+    assert!(not_modified(&[(
+      "if-modified-since",
+      "Wed, 01 May 2024 12:00:00 GMT"
+    )]));
+    assert!(not_modified(&[(
+      "if-modified-since",
+      "Thu, 02 May 2024 12:00:00 GMT"
+    )]));
+    assert!(!not_modified(&[(
+      "if-modified-since",
+      "Wed, 01 May 2024 11:59:59 GMT"
+    )]));
+    assert!(!not_modified(&[("if-modified-since", "yesterday")]));
+  }
+
+  #[test]
+  fn if_none_match_takes_precedence() {
+    // A matching date is ignored when the tag differs
+    assert!(!not_modified(&[
+      ("if-none-match", "\"x\""),
+      ("if-modified-since", "Thu, 02 May 2024 12:00:00 GMT"),
+    ]));
+    // And an old date is ignored when the tag matches
+    assert!(not_modified(&[
+      ("if-none-match", "\"abc\""),
+      ("if-modified-since", "Tue, 30 Apr 2024 12:00:00 GMT"),
+    ]));
+  }
+
+  #[test]
+  fn http_dates_round_trip() {
+    let date = http_date(&modified());
+    assert_eq!(date, "Wed, 01 May 2024 12:00:00 GMT");
+    assert_eq!(parse_http_date(&date), Some(modified()));
+    assert_eq!(
+      parse_http_date(" Wed, 01 May 2024 12:00:00 GMT "),
+      Some(modified())
+    );
+    assert_eq!(parse_http_date("2024-05-01"), None);
+  }
+
+  #[test]
+  fn weak_etags() {
+    assert_eq!(weaken("\"abc\""), "W/\"abc\"");
+    assert_eq!(weaken("W/\"abc\""), "W/\"abc\"");
+    assert!(is_weak("W/\"abc\""));
+    assert!(!is_weak("\"abc\""));
+  }
+}
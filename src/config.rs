@@ -1,13 +1,36 @@
 use std::collections::HashMap;
 use std::env;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
 use std::path::PathBuf;
 use std::path::MAIN_SEPARATOR_STR;
+use std::time::Duration;
 
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use clap::CommandFactory;
+use clap::FromArgMatches;
+use globset::GlobBuilder;
+use globset::GlobMatcher;
 use normalize_path::NormalizePath;
 use pathdiff::diff_paths;
 
+use crate::clean_urls::TrailingSlash;
 use crate::cli::CliCommand;
+use crate::compress::CompressionLevels;
+use crate::config_file::ConfigFile;
+use crate::cors::CorsPolicy;
+use crate::headers_file::HEADERS_FILE_NAME;
+use crate::hidden::HiddenFiles;
+use crate::proxy::ProxyRule;
+use crate::rules::parse_redirects_file;
+use crate::rules::Rule;
+use crate::rules::REDIRECTS_FILE_NAME;
+use crate::utils::size::SizeUnits;
 
 #[derive(Default, Debug)]
 pub struct Config {
@@ -15,9 +38,25 @@ pub struct Config {
   pub serve_dir_fmt: String,
   pub address: String,
   pub port: usize,
+  pub protocol: String,
+  pub tls_cert: Option<PathBuf>,
+  pub tls_key: Option<PathBuf>,
+  pub tls_self_signed: bool,
   pub spa: bool,
-  pub cors: bool,
+  pub index: Vec<String>,
+  pub dir_listing: bool,
+  pub hidden: HiddenFiles,
+  pub dir_template: Option<PathBuf>,
+  pub render_markdown: bool,
+  pub size_units: SizeUnits,
+  pub clean_urls: bool,
+  pub clean_urls_redirect: bool,
+  pub trailing_slash: TrailingSlash,
+  pub cors: Option<CorsPolicy>,
   pub compress: bool,
+  pub compression_levels: CompressionLevels,
+  pub compress_cache_size: usize,
+  pub etag_hash: bool,
   pub sab: bool,
   pub domain: String,
   pub domain_pretty: String,
@@ -28,15 +67,210 @@ pub struct Config {
   pub watch_dir: PathBuf,
   pub no_watch_inject: bool,
   pub stream_buffer_size: usize,
+  pub shutdown_timeout: Duration,
+  pub config_file: Option<PathBuf>,
+  pub path_headers: Vec<PathHeaders>,
+  pub rules: Vec<Rule>,
+  pub proxies: Vec<ProxyRule>,
+  pub sources: HashMap<String, ConfigSource>,
+}
+
+/// Where the value of an option came from
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum ConfigSource {
+  #[default]
+  Default,
+  Cli,
+  Env(String),
+  ConfigFile,
+}
+
+impl ConfigSource {
+  /// Values from the command line or environment take precedence over the config file
+  pub fn is_explicit(&self) -> bool {
+    matches!(self, ConfigSource::Cli | ConfigSource::Env(_))
+  }
+}
+
+impl Display for ConfigSource {
+  fn fmt(
+    &self,
+    f: &mut Formatter<'_>,
+  ) -> fmt::Result {
+    match self {
+      ConfigSource::Default => write!(f, "default"),
+      ConfigSource::Cli => write!(f, "cli"),
+      ConfigSource::Env(name) => write!(f, "env {}", name),
+      ConfigSource::ConfigFile => write!(f, "config file"),
+    }
+  }
+}
+
+/// Headers applied to responses for request paths matching a glob
+#[derive(Debug)]
+pub struct PathHeaders {
+  pub pattern: GlobMatcher,
+  pub headers: HashMap<String, Vec<String>>,
 }
 
 impl Config {
-  pub fn from_cli() -> anyhow::Result<Self> {
-    let command = CliCommand::parse();
+  /// The first index file that exists in "dir"
+  pub fn find_index(
+    &self,
+    dir: &Path,
+  ) -> Option<PathBuf> {
+    self
+      .index
+      .iter()
+      .map(|index| dir.join(index))
+      .find(|index_path| index_path.is_file())
+  }
+
+  /// Paths that are never listed or served: the config file, the
+  /// "_redirects" and "_headers" files, and hidden or ignored files
+  pub fn is_hidden(
+    &self,
+    path: &Path,
+  ) -> bool {
+    let path = path.normalize();
+    self
+      .config_file
+      .as_ref()
+      .is_some_and(|config_file| config_file == &path)
+      || path == self.serve_dir_abs.join(REDIRECTS_FILE_NAME)
+      || path == self.serve_dir_abs.join(HEADERS_FILE_NAME)
+      || self.hidden.is_hidden(&path)
+  }
+
+  /// Where the value of an option (identified by its CliCommand field name) came from
+  pub fn source(
+    &self,
+    id: &str,
+  ) -> ConfigSource {
+    self.sources.get(id).cloned().unwrap_or_default()
+  }
+
+  pub fn from_cli(matches: &ArgMatches) -> anyhow::Result<Self> {
+    let cli = CliCommand::command();
+    let mut command = CliCommand::from_arg_matches(matches)?;
     let Ok(cwd) = env::current_dir() else {
       return Err(anyhow::anyhow!("Unable to get cwd"));
     };
 
+    let mut sources = HashMap::<String, ConfigSource>::new();
+    for arg in cli.get_arguments() {
+      let id = arg.get_id().as_str();
+      let source = match matches.value_source(id) {
+        Some(ValueSource::CommandLine) => ConfigSource::Cli,
+        Some(ValueSource::EnvVariable) => ConfigSource::Env(
+          arg
+            .get_env()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        ),
+        _ => ConfigSource::Default,
+      };
+      sources.insert(id.to_string(), source);
+    }
+
+    // Values from the config file fill in anything not passed on the
+    // command line or through environment variables
+    let config_file = ConfigFile::discover(
+      command.config.as_deref(),
+      &[&cwd, &cwd.join(&command.serve_dir)],
+    )?;
+
+    let mut file_headers = HashMap::<String, Vec<String>>::new();
+    let mut file_auth = HashMap::<String, String>::new();
+    let mut path_headers = Vec::<PathHeaders>::new();
+    let mut file_rules = Vec::<Rule>::new();
+    let mut proxies = Vec::<ProxyRule>::new();
+
+    let config_file_path = match config_file {
+      Some((file_path, file)) => {
+        let file_path = cwd.join(file_path).normalize();
+        let base_dir = file_path.parent().unwrap_or(&cwd).to_path_buf();
+
+        merge_config_file(&mut command, &mut sources, &file_path, &base_dir, &file)?;
+
+        for (key, values) in file.headers {
+          file_headers
+            .entry(key)
+            .or_default()
+            .extend(values.into_vec());
+        }
+
+        file_auth = file.auth;
+
+        for (prefix, upstream) in file.proxy {
+          match ProxyRule::new(&prefix, &upstream) {
+            Ok(rule) => proxies.push(rule),
+            Err(error) => {
+              return Err(anyhow::anyhow!(
+                "Invalid config file {:?} at key \"proxy.{}\"\n{}",
+                file_path,
+                prefix,
+                error
+              ));
+            }
+          }
+        }
+
+        for (i, entry) in file.path_headers.into_iter().enumerate() {
+          let Ok(glob) = GlobBuilder::new(&entry.path)
+            .literal_separator(true)
+            .build()
+          else {
+            return Err(anyhow::anyhow!(
+              "Invalid config file {:?} at key \"path-headers[{}].path\"\nInvalid glob \"{}\"",
+              file_path,
+              i,
+              entry.path
+            ));
+          };
+
+          let mut headers = HashMap::<String, Vec<String>>::new();
+          for (key, values) in entry.headers {
+            headers.entry(key).or_default().extend(values.into_vec());
+          }
+
+          path_headers.push(PathHeaders {
+            pattern: glob.compile_matcher(),
+            headers,
+          });
+        }
+
+        for (i, entry) in file.redirects.into_iter().enumerate() {
+          let query = entry.query.into_iter().collect::<Vec<(String, String)>>();
+          match Rule::new(&entry.from, &entry.to, entry.status, entry.force, &query) {
+            Ok(rule) => file_rules.push(rule),
+            Err(error) => {
+              return Err(anyhow::anyhow!(
+                "Invalid config file {:?} at key \"redirects[{}]\"\n{}",
+                file_path,
+                i,
+                error
+              ));
+            }
+          }
+        }
+
+        Some(file_path)
+      }
+      None => None,
+    };
+
+    // PaaS providers assign the port through $PORT
+    if !sources.get("port").is_some_and(ConfigSource::is_explicit) {
+      if let Ok(port) = env::var("PORT") {
+        let Ok(port) = port.parse::<usize>() else {
+          return Err(anyhow::anyhow!("Unable to parse PORT \"{}\"", port));
+        };
+        command.port = port;
+        sources.insert("port".to_string(), ConfigSource::Env("PORT".to_string()));
+      }
+    }
+
     let domain = format!("{}:{}", command.address, command.port);
     let mut domain_pretty = domain.clone();
     if command.address == "0.0.0.0" || command.address == "::" {
@@ -44,6 +278,23 @@ impl Config {
       domain_pretty = format!("127.0.0.1:{}", command.port)
     }
 
+    let tls_enabled = match (&command.tls_cert, &command.tls_key) {
+      (Some(_), Some(_)) if command.tls_self_signed => {
+        return Err(anyhow::anyhow!(
+          "--tls-self-signed cannot be combined with --tls-cert"
+        ));
+      }
+      (Some(_), Some(_)) => true,
+      (Some(_), None) => return Err(anyhow::anyhow!("--tls-cert requires --tls-key")),
+      (None, Some(_)) => return Err(anyhow::anyhow!("--tls-key requires --tls-cert")),
+      (None, None) => command.tls_self_signed,
+    };
+
+    let protocol = match tls_enabled {
+      true => "https".to_string(),
+      false => "http".to_string(),
+    };
+
     let serve_dir_abs: PathBuf;
     let serve_dir_rel: PathBuf;
 
@@ -56,6 +307,35 @@ impl Config {
       serve_dir_rel = diff_paths(&serve_dir_abs, &cwd).unwrap();
     }
 
+    // Rules from _redirects come before the ones in the config file
+    let redirects_file = serve_dir_abs.join(REDIRECTS_FILE_NAME);
+    let mut rules = match fs::read_to_string(&redirects_file) {
+      Ok(contents) => match parse_redirects_file(&contents) {
+        Ok(rules) => rules,
+        Err(error) => {
+          return Err(anyhow::anyhow!(
+            "Invalid redirects file {:?} at {}",
+            redirects_file,
+            error
+          ));
+        }
+      },
+      Err(_) => Vec::new(),
+    };
+    rules.extend(file_rules);
+
+    let dir_template = command.dir_template.map(|path| cwd.join(path).normalize());
+    if let Some(dir_template) = &dir_template {
+      if !dir_template.is_file() {
+        return Err(anyhow::anyhow!(
+          "Unable to find directory template {:?}",
+          dir_template
+        ));
+      }
+    }
+
+    let hidden = HiddenFiles::new(&serve_dir_abs, command.show_hidden, &command.ignore)?;
+
     let mut headers = HashMap::<String, Vec<String>>::new();
 
     if command.sab {
@@ -80,7 +360,7 @@ impl Config {
       );
     }
 
-    let mut basic_auth = HashMap::<String, String>::new();
+    let mut basic_auth = file_auth;
 
     for val in command.basic_auth {
       let Some((key, value)) = val.split_once(":") else {
@@ -91,16 +371,30 @@ impl Config {
       basic_auth.insert(key, value);
     }
 
-    if command.cors {
-      headers.insert(
-        "Access-Control-Allow-Origin".to_string(),
-        vec!["*".to_string()],
-      );
-    } else {
-      headers.insert(
-        "Access-Control-Allow-Origin".to_string(),
-        vec!["null".to_string()],
-      );
+    let cors_enabled = command.cors || !command.cors_origin.is_empty();
+
+    // Any origin together with credentials would let every site make
+    // authenticated reads
+    if cors_enabled
+      && command.cors_credentials
+      && (command.cors_origin.is_empty() || command.cors_origin.iter().any(|origin| origin == "*"))
+    {
+      return Err(anyhow::anyhow!(
+        "--cors-credentials requires an explicit --cors-origin list"
+      ));
+    }
+
+    let cors = match cors_enabled {
+      true => Some(CorsPolicy::new(
+        &command.cors_origin,
+        command.cors_credentials,
+        command.cors_max_age,
+      )?),
+      false => None,
+    };
+
+    for rule in command.proxy {
+      proxies.push(ProxyRule::parse(&rule)?);
     }
 
     if command.cache_time == 0 {
@@ -115,6 +409,10 @@ impl Config {
       );
     }
 
+    for (key, values) in file_headers {
+      headers.entry(key).or_default().extend(values);
+    }
+
     for header in command.headers {
       let Some((key, value)) = header.split_once(":") else {
         return Err(anyhow::anyhow!("Unable to parse header"));
@@ -130,18 +428,255 @@ impl Config {
       domain,
       domain_pretty,
       spa: command.spa,
-      cors: command.cors,
+      index: command.index,
+      dir_listing: !command.no_dir_listing,
+      hidden,
+      dir_template,
+      render_markdown: command.render_markdown,
+      size_units: command.size_units,
+      clean_urls: command.clean_urls || command.clean_urls_redirect,
+      clean_urls_redirect: command.clean_urls_redirect,
+      trailing_slash: command.trailing_slash,
+      cors,
       compress: command.compress,
+      compress_cache_size: command.compress_cache_size.saturating_mul(1024 * 1024),
+      compression_levels: CompressionLevels {
+        brotli: command.brotli_level,
+        gzip: command.gzip_level,
+        zstd: command.zstd_level,
+      },
+      etag_hash: command.etag_hash,
       sab: command.sab,
       address: command.address,
       basic_auth,
       port: command.port,
+      protocol,
+      tls_cert: command.tls_cert,
+      tls_key: command.tls_key,
+      tls_self_signed: command.tls_self_signed,
       headers,
       quiet: command.quiet,
       watch: command.watch,
       watch_dir: command.watch_dir.unwrap_or(serve_dir_abs),
       no_watch_inject: command.no_watch_inject,
       stream_buffer_size: command.stream_buffer_size,
+      shutdown_timeout: Duration::from_secs(command.shutdown_timeout),
+      config_file: config_file_path,
+      path_headers,
+      rules,
+      proxies,
+      sources,
     })
   }
 }
+
+/// Copy values from the config file into the parsed command, skipping
+/// any option that was explicitly passed on the command line or environment
+fn merge_config_file(
+  command: &mut CliCommand,
+  sources: &mut HashMap<String, ConfigSource>,
+  file_path: &Path,
+  base_dir: &Path,
+  file: &ConfigFile,
+) -> anyhow::Result<()> {
+  let resolve = |path: &Option<PathBuf>| path.as_ref().map(|path| base_dir.join(path));
+
+  merge(
+    &mut command.serve_dir,
+    resolve(&file.serve_dir),
+    "serve_dir",
+    sources,
+  );
+  merge(
+    &mut command.address,
+    file.address.clone(),
+    "address",
+    sources,
+  );
+  merge(&mut command.port, file.port, "port", sources);
+  merge(
+    &mut command.tls_cert,
+    resolve(&file.tls_cert).map(Some),
+    "tls_cert",
+    sources,
+  );
+  merge(
+    &mut command.tls_key,
+    resolve(&file.tls_key).map(Some),
+    "tls_key",
+    sources,
+  );
+  merge(
+    &mut command.tls_self_signed,
+    file.tls_self_signed,
+    "tls_self_signed",
+    sources,
+  );
+  merge(&mut command.spa, file.spa, "spa", sources);
+  merge(&mut command.index, file.index.clone(), "index", sources);
+  merge(
+    &mut command.no_dir_listing,
+    file.no_dir_listing,
+    "no_dir_listing",
+    sources,
+  );
+  merge(
+    &mut command.show_hidden,
+    file.show_hidden,
+    "show_hidden",
+    sources,
+  );
+  merge(&mut command.ignore, file.ignore.clone(), "ignore", sources);
+  merge(
+    &mut command.dir_template,
+    resolve(&file.dir_template).map(Some),
+    "dir_template",
+    sources,
+  );
+  merge(
+    &mut command.render_markdown,
+    file.render_markdown,
+    "render_markdown",
+    sources,
+  );
+  merge(
+    &mut command.size_units,
+    file.size_units,
+    "size_units",
+    sources,
+  );
+  merge(
+    &mut command.clean_urls,
+    file.clean_urls,
+    "clean_urls",
+    sources,
+  );
+  merge(
+    &mut command.clean_urls_redirect,
+    file.clean_urls_redirect,
+    "clean_urls_redirect",
+    sources,
+  );
+  merge(
+    &mut command.trailing_slash,
+    file.trailing_slash,
+    "trailing_slash",
+    sources,
+  );
+  merge(
+    &mut command.cache_time,
+    file.cache_time,
+    "cache_time",
+    sources,
+  );
+  merge(&mut command.etag_hash, file.etag_hash, "etag_hash", sources);
+  merge(&mut command.compress, file.compress, "compress", sources);
+  merge(
+    &mut command.compress_cache_size,
+    file.compress_cache_size,
+    "compress_cache_size",
+    sources,
+  );
+  merge(
+    &mut command.brotli_level,
+    check_range(file_path, "brotli-level", file.brotli_level, 0..=11)?,
+    "brotli_level",
+    sources,
+  );
+  merge(
+    &mut command.gzip_level,
+    check_range(file_path, "gzip-level", file.gzip_level, 0..=9)?,
+    "gzip_level",
+    sources,
+  );
+  merge(
+    &mut command.zstd_level,
+    check_range(file_path, "zstd-level", file.zstd_level, 1..=22)?,
+    "zstd_level",
+    sources,
+  );
+  merge(&mut command.cors, file.cors, "cors", sources);
+  merge(
+    &mut command.cors_origin,
+    file.cors_origin.clone(),
+    "cors_origin",
+    sources,
+  );
+  merge(
+    &mut command.cors_credentials,
+    file.cors_credentials,
+    "cors_credentials",
+    sources,
+  );
+  merge(
+    &mut command.cors_max_age,
+    file.cors_max_age,
+    "cors_max_age",
+    sources,
+  );
+  merge(&mut command.sab, file.shared_array_buffer, "sab", sources);
+  merge(&mut command.quiet, file.quiet, "quiet", sources);
+  merge(&mut command.watch, file.watch, "watch", sources);
+  merge(
+    &mut command.watch_dir,
+    resolve(&file.watch_dir).map(Some),
+    "watch_dir",
+    sources,
+  );
+  merge(
+    &mut command.no_watch_inject,
+    file.no_watch_inject,
+    "no_watch_inject",
+    sources,
+  );
+  merge(
+    &mut command.stream_buffer_size,
+    file.stream_buffer_size,
+    "stream_buffer_size",
+    sources,
+  );
+  merge(
+    &mut command.shutdown_timeout,
+    file.shutdown_timeout,
+    "shutdown_timeout",
+    sources,
+  );
+
+  Ok(())
+}
+
+/// Apply the range checks clap does for the matching flag to a value
+/// from the config file
+fn check_range<T: PartialOrd + Display>(
+  file_path: &Path,
+  key: &str,
+  value: Option<T>,
+  range: RangeInclusive<T>,
+) -> anyhow::Result<Option<T>> {
+  match value {
+    Some(value) if !range.contains(&value) => Err(anyhow::anyhow!(
+      "Invalid config file {:?} at key \"{}\"\nExpected a value from {} to {}, got {}",
+      file_path,
+      key,
+      range.start(),
+      range.end(),
+      value
+    )),
+    value => Ok(value),
+  }
+}
+
+fn merge<T>(
+  target: &mut T,
+  value: Option<T>,
+  id: &str,
+  sources: &mut HashMap<String, ConfigSource>,
+) {
+  if sources.get(id).is_some_and(ConfigSource::is_explicit) {
+    return;
+  }
+  if let Some(value) = value {
+    *target = value;
+    sources.insert(id.to_string(), ConfigSource::ConfigFile);
+  }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::clean_urls::TrailingSlash;
+use crate::utils::size::SizeUnits;
+
+/// File names searched for (in order) when no --config is supplied
+pub const CONFIG_FILE_NAMES: [&str; 2] = ["http-server.toml", "http-server.json"];
+
+/// Optional configuration file mirroring the CLI options. Every field is
+/// optional so that flags supplied on the command line can take precedence
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ConfigFile {
+  pub serve_dir: Option<PathBuf>,
+  pub address: Option<String>,
+  pub port: Option<usize>,
+  pub tls_cert: Option<PathBuf>,
+  pub tls_key: Option<PathBuf>,
+  pub tls_self_signed: Option<bool>,
+  pub spa: Option<bool>,
+  pub index: Option<Vec<String>>,
+  pub no_dir_listing: Option<bool>,
+  pub show_hidden: Option<bool>,
+  pub ignore: Option<Vec<String>>,
+  pub dir_template: Option<PathBuf>,
+  pub render_markdown: Option<bool>,
+  pub size_units: Option<SizeUnits>,
+  pub clean_urls: Option<bool>,
+  pub clean_urls_redirect: Option<bool>,
+  pub trailing_slash: Option<TrailingSlash>,
+  pub cache_time: Option<usize>,
+  pub etag_hash: Option<bool>,
+  pub compress: Option<bool>,
+  pub compress_cache_size: Option<usize>,
+  pub brotli_level: Option<u32>,
+  pub gzip_level: Option<u32>,
+  pub zstd_level: Option<i32>,
+  pub cors: Option<bool>,
+  pub cors_origin: Option<Vec<String>>,
+  pub cors_credentials: Option<bool>,
+  pub cors_max_age: Option<u64>,
+  pub shared_array_buffer: Option<bool>,
+  pub quiet: Option<bool>,
+  pub watch: Option<bool>,
+  pub watch_dir: Option<PathBuf>,
+  pub no_watch_inject: Option<bool>,
+  pub stream_buffer_size: Option<usize>,
+  pub shutdown_timeout: Option<u64>,
+  /// Headers applied to every response
+  #[serde(default)]
+  pub headers: HashMap<String, HeaderValues>,
+  /// Basic auth users, username mapped to password
+  #[serde(default)]
+  pub auth: HashMap<String, String>,
+  /// Paths forwarded to another server, path prefix mapped to upstream URL
+  #[serde(default)]
+  pub proxy: HashMap<String, String>,
+  /// Headers applied to responses for paths matching a glob
+  #[serde(default)]
+  pub path_headers: Vec<PathHeadersEntry>,
+  #[serde(default)]
+  pub redirects: Vec<RedirectEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum HeaderValues {
+  One(String),
+  Many(Vec<String>),
+}
+
+impl HeaderValues {
+  pub fn into_vec(self) -> Vec<String> {
+    match self {
+      HeaderValues::One(value) => vec![value],
+      HeaderValues::Many(values) => values,
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PathHeadersEntry {
+  pub path: String,
+  pub headers: HashMap<String, HeaderValues>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedirectEntry {
+  pub from: String,
+  pub to: String,
+  #[serde(default = "default_redirect_status")]
+  pub status: u16,
+  /// Apply the rule even when a file exists at the requested path
+  #[serde(default)]
+  pub force: bool,
+  /// Query parameters the request must have, ":name" values capture them
+  #[serde(default)]
+  pub query: HashMap<String, String>,
+}
+
+fn default_redirect_status() -> u16 {
+  301
+}
+
+impl ConfigFile {
+  /// Find a config file, either the one supplied explicitly or the first
+  /// well known file name in the cwd and then the serve directory
+  pub fn discover(
+    explicit: Option<&Path>,
+    search_dirs: &[&Path],
+  ) -> anyhow::Result<Option<(PathBuf, Self)>> {
+    if let Some(file_path) = explicit {
+      if !file_path.is_file() {
+        return Err(anyhow::anyhow!("Config file not found {:?}", file_path));
+      }
+      return Ok(Some((file_path.to_path_buf(), Self::load(file_path)?)));
+    }
+
+    for dir in search_dirs {
+      for file_name in CONFIG_FILE_NAMES {
+        let file_path = dir.join(file_name);
+        if file_path.is_file() {
+          let config_file = Self::load(&file_path)?;
+          return Ok(Some((file_path, config_file)));
+        }
+      }
+    }
+
+    Ok(None)
+  }
+
+  pub fn load(file_path: &Path) -> anyhow::Result<Self> {
+    let contents = fs::read_to_string(file_path)?;
+
+    let is_json = file_path
+      .extension()
+      .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+
+    let result = match is_json {
+      true => {
+        let mut deserializer = serde_json::Deserializer::from_str(&contents);
+        serde_path_to_error::deserialize(&mut deserializer)
+          .map_err(|error| (error.path().to_string(), error.into_inner().to_string()))
+      }
+      false => {
+        let deserializer = toml::Deserializer::new(&contents);
+        serde_path_to_error::deserialize(deserializer)
+          .map_err(|error| (error.path().to_string(), error.into_inner().to_string()))
+      }
+    };
+
+    match result {
+      Ok(config_file) => Ok(config_file),
+      Err((key, message)) if key == "." => Err(anyhow::anyhow!(
+        "Invalid config file {:?}\n{}",
+        file_path,
+        message.trim()
+      )),
+      Err((key, message)) => Err(anyhow::anyhow!(
+        "Invalid config file {:?} at key \"{}\"\n{}",
+        file_path,
+        key,
+        message.trim()
+      )),
+    }
+  }
+}
@@ -0,0 +1,133 @@
+use globset::GlobBuilder;
+use globset::GlobMatcher;
+use hyper::header::HeaderMap;
+use hyper::http::response::Builder as ResponseBuilder;
+use hyper::Method;
+
+/// Cross-origin policy applied to every response when --cors is enabled
+#[derive(Debug, Clone)]
+pub struct CorsPolicy {
+  /// Allowed origins, any origin is allowed when empty. Never empty
+  /// with credentials, Config::from_cli refuses that combination
+  origins: Vec<GlobMatcher>,
+  credentials: bool,
+  max_age: u64,
+}
+
+impl CorsPolicy {
+  pub fn new(
+    origins: &[String],
+    credentials: bool,
+    max_age: u64,
+  ) -> anyhow::Result<Self> {
+    let mut matchers = Vec::new();
+
+    for origin in origins {
+      if origin == "*" {
+        return Ok(Self {
+          origins: Vec::new(),
+          credentials,
+          max_age,
+        });
+      }
+
+      let Ok(glob) = GlobBuilder::new(&origin.to_lowercase()).build() else {
+        return Err(anyhow::anyhow!(
+          "Unable to parse CORS origin \"{}\"",
+          origin
+        ));
+      };
+      matchers.push(glob.compile_matcher());
+    }
+
+    Ok(Self {
+      origins: matchers,
+      credentials,
+      max_age,
+    })
+  }
+
+  /// A preflight is an OPTIONS request asking whether the real request
+  /// may be sent
+  pub fn is_preflight(
+    method: &Method,
+    headers: &HeaderMap,
+  ) -> bool {
+    method == Method::OPTIONS
+      && headers.contains_key("Origin")
+      && headers.contains_key("Access-Control-Request-Method")
+  }
+
+  /// Headers for a regular (non-preflight) request
+  pub fn apply(
+    &self,
+    mut res: ResponseBuilder,
+    headers: &HeaderMap,
+  ) -> ResponseBuilder {
+    // The response differs per Origin unless every origin gets "*"
+    if !self.origins.is_empty() {
+      res = res.header("Vary", "Origin");
+    }
+
+    let Some(allow_origin) = self.allow_origin(headers) else {
+      return res;
+    };
+
+    res = res.header("Access-Control-Allow-Origin", allow_origin);
+    if self.credentials {
+      res = res.header("Access-Control-Allow-Credentials", "true");
+    }
+    res
+  }
+
+  /// Headers answering a preflight request
+  pub fn preflight(
+    &self,
+    res: ResponseBuilder,
+    headers: &HeaderMap,
+    allowed_methods: &str,
+  ) -> ResponseBuilder {
+    let allowed = self.allow_origin(headers).is_some();
+    let mut res = self.apply(res, headers);
+    if !allowed {
+      return res;
+    }
+
+    res = res
+      .header("Access-Control-Allow-Methods", allowed_methods)
+      .header("Access-Control-Max-Age", self.max_age.to_string());
+
+    // Any request header is fine for a static server, so echo them back
+    if let Some(request_headers) = headers.get("Access-Control-Request-Headers") {
+      res = res
+        .header("Access-Control-Allow-Headers", request_headers)
+        .header("Vary", "Access-Control-Request-Headers");
+    }
+
+    res
+  }
+
+  /// Value for Access-Control-Allow-Origin, None when the origin is not
+  /// allowed. Only origins on the list are echoed back
+  fn allow_origin(
+    &self,
+    headers: &HeaderMap,
+  ) -> Option<String> {
+    if self.origins.is_empty() {
+      return Some("*".to_string());
+    }
+
+    let origin = headers
+      .get("Origin")
+      .and_then(|value| value.to_str().ok())?;
+    if !self
+      .origins
+      .iter()
+      .any(|matcher| matcher.is_match(origin.to_lowercase()))
+    {
+      return None;
+    }
+
+    Some(origin.to_string())
+  }
+}
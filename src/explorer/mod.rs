@@ -4,54 +4,244 @@ mod unix;
 #[cfg(windows)]
 mod windows;
 
+use std::fmt::Write;
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 
 use chrono::DateTime;
 use chrono::Utc;
+use globset::GlobBuilder;
+use globset::GlobMatcher;
+use handlebars::Context;
 use handlebars::Handlebars;
+use handlebars::Helper;
+use handlebars::HelperResult;
+use handlebars::Output;
+use handlebars::RenderContext;
+use handlebars::RenderErrorReason;
 use normalize_path::NormalizePath;
+use serde::Serialize;
 use serde_json::json;
 
 #[cfg(unix)]
 use self::unix::get_meta_mode;
-#[cfg(unix)]
-use self::unix::get_meta_size;
 #[cfg(windows)]
 use self::windows::get_meta_mode;
-#[cfg(windows)]
-use self::windows::get_meta_size;
 use crate::config::Config;
+use crate::markdown::render_markdown;
+use crate::utils::query::parse_query;
+use crate::utils::size::format_size;
+use crate::utils::size::SizeUnits;
 
 const DIR_PAGE: &str = include_str!("./dir.hbs");
+const DATE_FORMAT: &str = "%d %b %Y %H:%M";
 const RELOAD_SCRIPT: &str = include_str!("./reload.mjs");
 
 pub fn reload_script() -> &'static str {
   RELOAD_SCRIPT
 }
 
-pub fn render_directory_explorer(
+/// Contents of a directory, served as HTML or JSON
+#[derive(Debug, Serialize)]
+pub struct Listing {
+  /// Request path of the directory
+  pub path: String,
+  /// Absolute link to the parent directory, None at the root
+  pub parent: Option<String>,
+  pub entries: Vec<ListingEntry>,
+  #[serde(skip)]
+  pub query: ListingQuery,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListingEntry {
+  pub name: String,
+  #[serde(rename = "type")]
+  pub kind: EntryKind,
+  /// Size in bytes
+  pub size: u64,
+  pub mode: String,
+  /// Last modification time, named like the "mtime" sort key
+  #[serde(rename = "mtime")]
+  pub modified: DateTime<Utc>,
+  /// Link relative to the directory
+  pub relative_link: String,
+  /// Link from the root of the server
+  pub absolute_link: String,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+  File,
+  Directory,
+}
+
+/// Sorting and filtering requested through "?sort=size&order=desc&filter=*.js"
+#[derive(Debug, Default, Clone)]
+pub struct ListingQuery {
+  pub sort: SortKey,
+  pub order: SortOrder,
+  /// Substring of the entry name, or a glob when it contains "*", "?" or "["
+  pub filter: Option<String>,
+}
+
+enum NameFilter {
+  Substring(String),
+  Glob(GlobMatcher),
+}
+
+impl NameFilter {
+  fn is_match(
+    &self,
+    name: &str,
+  ) -> bool {
+    match self {
+      NameFilter::Substring(filter) => name.to_lowercase().contains(filter),
+      NameFilter::Glob(matcher) => matcher.is_match(name),
+    }
+  }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SortKey {
+  #[default]
+  Name,
+  Size,
+  Mtime,
+  Extension,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+  #[default]
+  Asc,
+  Desc,
+}
+
+impl SortKey {
+  const ALL: [SortKey; 4] = [
+    SortKey::Name,
+    SortKey::Size,
+    SortKey::Mtime,
+    SortKey::Extension,
+  ];
+
+  fn as_str(&self) -> &'static str {
+    match self {
+      SortKey::Name => "name",
+      SortKey::Size => "size",
+      SortKey::Mtime => "mtime",
+      SortKey::Extension => "extension",
+    }
+  }
+}
+
+impl SortOrder {
+  fn as_str(&self) -> &'static str {
+    match self {
+      SortOrder::Asc => "asc",
+      SortOrder::Desc => "desc",
+    }
+  }
+}
+
+impl ListingQuery {
+  /// Unknown values fall back to the defaults
+  pub fn from_query(query: Option<&str>) -> Self {
+    let mut listing_query = Self::default();
+
+    for (key, value) in parse_query(query) {
+      match key.as_str() {
+        "sort" => {
+          if let Some(sort) = SortKey::ALL.into_iter().find(|sort| sort.as_str() == value) {
+            listing_query.sort = sort;
+          }
+        }
+        "order" => {
+          listing_query.order = match value.as_str() {
+            "desc" => SortOrder::Desc,
+            _ => SortOrder::Asc,
+          }
+        }
+        "filter" if !value.is_empty() => listing_query.filter = Some(value),
+        _ => {}
+      }
+    }
+
+    listing_query
+  }
+
+  /// Query string sorting by "sort", toggling the order if already sorted by it
+  fn sort_link(
+    &self,
+    sort: SortKey,
+  ) -> String {
+    let order = match self.sort == sort && self.order == SortOrder::Asc {
+      true => SortOrder::Desc,
+      false => SortOrder::Asc,
+    };
+    let mut link = format!("?sort={}&order={}", sort.as_str(), order.as_str());
+    if let Some(filter) = &self.filter {
+      link.push_str(&format!("&filter={}", urlencoding::encode(filter)));
+    }
+    link
+  }
+
+  fn name_filter(&self) -> anyhow::Result<Option<NameFilter>> {
+    let Some(filter) = &self.filter else {
+      return Ok(None);
+    };
+
+    if filter.contains(['*', '?', '[']) {
+      let Ok(glob) = GlobBuilder::new(filter).case_insensitive(true).build() else {
+        return Err(anyhow::anyhow!("Invalid filter \"{}\"", filter));
+      };
+      return Ok(Some(NameFilter::Glob(glob.compile_matcher())));
+    }
+
+    Ok(Some(NameFilter::Substring(filter.to_lowercase())))
+  }
+}
+
+/// Read the entries of "file_path", requested as "req_uri"
+pub fn read_listing(
   config: &Config,
   req_uri: &str,
   file_path: &Path,
-) -> anyhow::Result<String> {
+  query: ListingQuery,
+) -> anyhow::Result<Listing> {
   let dir_path = PathBuf::from(file_path);
   let dir = fs::read_dir(&dir_path)?;
-  let mut files = Vec::<(String, String, String, String, String, String)>::new();
-  let mut folders = Vec::<(String, String, String, String)>::new();
+  let mut entries = Vec::<ListingEntry>::new();
+  let name_filter = query.name_filter()?;
 
   for item in dir {
-    let Ok(item) = item else {
-      return Ok("Access error".to_string());
-    };
+    let item = item?;
+    let name = item.file_name().to_string_lossy().to_string();
 
-    let meta = item.metadata()?;
+    if config.is_hidden(&item.path()) {
+      continue;
+    }
+
+    if name_filter
+      .as_ref()
+      .is_some_and(|name_filter| !name_filter.is_match(&name))
+    {
+      continue;
+    }
+
+    // Follow symlinks so entries are described as they would be served
+    let meta = match fs::metadata(item.path()) {
+      Ok(meta) => meta,
+      Err(_) => item.metadata()?,
+    };
     let meta_mode = get_meta_mode(&meta);
     let last_modified: DateTime<Utc> = meta.modified()?.into();
 
     let Some(abs_path) = pathdiff::diff_paths(item.path(), &config.serve_dir_abs) else {
-      return Ok(format!(
+      return Err(anyhow::anyhow!(
         "Unable to diff path (absolute) \n\t{:?}\n\t{:?}",
         item.path(),
         config.serve_dir_abs
@@ -60,68 +250,284 @@ pub fn render_directory_explorer(
 
     let Some(rel_path) = pathdiff::diff_paths(item.path(), config.serve_dir_abs.join(req_uri))
     else {
-      return Ok(format!(
+      return Err(anyhow::anyhow!(
         "Unable to diff path (relative) \n\t{:?}\n\t{:?}",
         item.path(),
         config.serve_dir_abs
       ));
     };
 
-    let abs_path_str = abs_path.to_str().unwrap().to_string();
-    let rel_path_str = rel_path.to_str().unwrap().to_string();
-
-    if item.file_type()?.is_dir() {
-      folders.push((
-        meta_mode.to_string(),
-        format!("{}", last_modified.format("%d %b %Y %H:%M")),
-        abs_path_str,
-        rel_path_str,
-      ));
-    } else {
-      let filename = PathBuf::from(item.file_name().into_string().unwrap());
-      let file_extension = match filename.extension() {
-        Some(ext) => ext.to_str().unwrap().to_string(),
-        None => "".to_string(),
-      };
-
-      let size = get_meta_size(&meta);
-      files.push((
-        file_extension,
-        meta_mode.to_string(),
-        format!("{}", last_modified.format("%d %b %Y %H:%M")),
-        size.to_string(),
-        abs_path_str,
-        rel_path_str,
-      ));
+    let kind = match meta.is_dir() {
+      true => EntryKind::Directory,
+      false => EntryKind::File,
     };
+
+    entries.push(ListingEntry {
+      name,
+      kind,
+      size: meta.len(),
+      mode: meta_mode.to_string(),
+      modified: last_modified,
+      relative_link: encode_link(&rel_path),
+      absolute_link: format!("/{}", encode_link(&abs_path)),
+    });
   }
 
   let mut parent = None::<String>;
   if let Some(up_one) = dir_path.parent() {
     if dir_path != config.serve_dir_abs.normalize() {
       let diff = pathdiff::diff_paths(up_one, &config.serve_dir_abs).unwrap();
-      let diff_str = diff.to_str().unwrap();
-      parent = Some(format!("/{}", diff_str));
+      parent = Some(format!("/{}", encode_link(&diff)));
     }
   }
 
-  folders.sort_by(|a, b| a.3.to_lowercase().cmp(&b.3.to_lowercase()));
-  files.sort_by(|a, b| a.5.to_lowercase().cmp(&b.5.to_lowercase()));
-
-  let handlebars = Handlebars::new();
-  let Ok(output) = handlebars.render_template(
-    DIR_PAGE,
-    &json!({
-      "parent": parent,
-      "path": req_uri,
-      "files": files,
-      "folders": folders,
-      "address": config.address.clone(),
-      "port": config.port.clone(),
-    }),
-  ) else {
-    return Ok("Unable to render page".to_string());
+  sort_entries(&mut entries, query.sort, query.order);
+
+  Ok(Listing {
+    path: format!("/{}", req_uri),
+    parent,
+    entries,
+    query,
+  })
+}
+
+/// Directories always come first, ties are broken by name
+fn sort_entries(
+  entries: &mut [ListingEntry],
+  sort: SortKey,
+  order: SortOrder,
+) {
+  let extension = |entry: &ListingEntry| {
+    Path::new(&entry.name)
+      .extension()
+      .map(|ext| ext.to_string_lossy().to_lowercase())
+      .unwrap_or_default()
+  };
+
+  entries.sort_by(|a, b| {
+    let by_name = a.name.to_lowercase().cmp(&b.name.to_lowercase());
+    let ordering = match sort {
+      SortKey::Name => by_name,
+      SortKey::Size => a.size.cmp(&b.size).then(by_name),
+      SortKey::Mtime => a.modified.cmp(&b.modified).then(by_name),
+      SortKey::Extension => extension(a).cmp(&extension(b)).then(by_name),
+    };
+    let ordering = match order {
+      SortOrder::Asc => ordering,
+      SortOrder::Desc => ordering.reverse(),
+    };
+    (b.kind == EntryKind::Directory)
+      .cmp(&(a.kind == EntryKind::Directory))
+      .then(ordering)
+  });
+}
+
+/// Entry as seen by templates, the JSON listing fields plus a few extras
+#[derive(Debug, Serialize)]
+struct TemplateEntry<'a> {
+  #[serde(flatten)]
+  entry: &'a ListingEntry,
+  /// Lowercase file extension without the dot, empty for directories
+  extension: String,
+  is_dir: bool,
+}
+
+/// A link to the listing of one of the directories in the current path
+#[derive(Debug, Serialize)]
+struct Breadcrumb {
+  name: String,
+  link: String,
+}
+
+/// Render a listing as HTML with the built-in template, or the file
+/// passed with --dir-template. The template is read on every request so
+/// edits show up on reload
+pub fn render_directory_explorer(
+  config: &Config,
+  dir_path: &Path,
+  listing: &Listing,
+) -> anyhow::Result<String> {
+  let (template_name, template) = match &config.dir_template {
+    Some(template_path) => match fs::read_to_string(template_path) {
+      Ok(template) => (template_path.to_string_lossy().to_string(), template),
+      Err(error) => {
+        return Err(anyhow::anyhow!(
+          "Unable to read directory template {:?}\n{}",
+          template_path,
+          error
+        ));
+      }
+    },
+    None => ("dir.hbs".to_string(), DIR_PAGE.to_string()),
   };
 
-  Ok(output)
+  let mut handlebars = Handlebars::new();
+  register_helpers(&mut handlebars, config.size_units);
+
+  if let Err(error) = handlebars.register_template_string(&template_name, template) {
+    return Err(anyhow::anyhow!("Invalid directory template\n{}", error));
+  }
+
+  let entries = listing
+    .entries
+    .iter()
+    .map(|entry| TemplateEntry {
+      entry,
+      extension: match entry.kind {
+        EntryKind::Directory => String::new(),
+        EntryKind::File => Path::new(&entry.name)
+          .extension()
+          .map(|ext| ext.to_string_lossy().to_lowercase())
+          .unwrap_or_default(),
+      },
+      is_dir: entry.kind == EntryKind::Directory,
+    })
+    .collect::<Vec<TemplateEntry>>();
+
+  let readme = find_readme(config, dir_path).and_then(|readme_path| {
+    let markdown = fs::read_to_string(&readme_path).ok()?;
+    Some(json!({
+      "name": readme_path.file_name()?.to_string_lossy(),
+      "html": render_markdown(&markdown),
+    }))
+  });
+
+  let context = json!({
+    "path": listing.path,
+    "parent": listing.parent,
+    "breadcrumbs": breadcrumbs(&listing.path),
+    "entries": entries,
+    "readme": readme,
+    "filter": listing.query.filter,
+    "sort": listing.query.sort.as_str(),
+    "order": listing.query.order.as_str(),
+    "sort_links": {
+      "name": listing.query.sort_link(SortKey::Name),
+      "size": listing.query.sort_link(SortKey::Size),
+      "mtime": listing.query.sort_link(SortKey::Mtime),
+      "extension": listing.query.sort_link(SortKey::Extension),
+    },
+    "server": {
+      "name": env!("CARGO_PKG_NAME"),
+      "version": env!("CARGO_PKG_VERSION"),
+      "address": config.address,
+      "port": config.port,
+      "url": format!("{}://{}", config.protocol, config.domain_pretty),
+    },
+  });
+
+  match handlebars.render(&template_name, &context) {
+    Ok(output) => Ok(output),
+    Err(error) => Err(anyhow::anyhow!(
+      "Unable to render directory template\n{}",
+      error
+    )),
+  }
+}
+
+/// "format_size" prints a size in bytes in the configured units and
+/// "format_date" prints a date, optionally with a strftime format
+///
+/// {{format_size size}}
+/// {{format_date mtime "%Y-%m-%d"}}
+fn register_helpers(
+  handlebars: &mut Handlebars,
+  size_units: SizeUnits,
+) {
+  handlebars.register_helper(
+    "format_size",
+    Box::new(
+      move |h: &Helper,
+            _: &Handlebars,
+            _: &Context,
+            _: &mut RenderContext,
+            out: &mut dyn Output|
+            -> HelperResult {
+        let Some(bytes) = h.param(0).and_then(|param| param.value().as_u64()) else {
+          return Err(RenderErrorReason::InvalidParamType("a size in bytes").into());
+        };
+        out.write(&format_size(bytes, size_units))?;
+        Ok(())
+      },
+    ),
+  );
+
+  handlebars.register_helper(
+    "format_date",
+    Box::new(
+      |h: &Helper,
+       _: &Handlebars,
+       _: &Context,
+       _: &mut RenderContext,
+       out: &mut dyn Output|
+       -> HelperResult {
+        let Some(date) = h
+          .param(0)
+          .and_then(|param| param.value().as_str())
+          .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+        else {
+          return Err(RenderErrorReason::InvalidParamType("an RFC 3339 date").into());
+        };
+        let format = h
+          .param(1)
+          .and_then(|param| param.value().as_str())
+          .unwrap_or(DATE_FORMAT);
+
+        let mut formatted = String::new();
+        if write!(formatted, "{}", date.with_timezone(&Utc).format(format)).is_err() {
+          return Err(
+            RenderErrorReason::Other(format!("Invalid date format \"{}\"", format)).into(),
+          );
+        }
+        out.write(&formatted)?;
+        Ok(())
+      },
+    ),
+  );
+}
+
+/// A README.md in "dir", shown beneath the listing
+fn find_readme(
+  config: &Config,
+  dir: &Path,
+) -> Option<PathBuf> {
+  fs::read_dir(dir)
+    .ok()?
+    .filter_map(|item| item.ok())
+    .map(|item| item.path())
+    .find(|path| {
+      path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().eq_ignore_ascii_case("readme.md"))
+        && path.is_file()
+        && !config.is_hidden(path)
+    })
+}
+
+/// Links to each directory from the root down to "path"
+fn breadcrumbs(path: &str) -> Vec<Breadcrumb> {
+  let mut link = "/".to_string();
+  let mut breadcrumbs = vec![Breadcrumb {
+    name: "/".to_string(),
+    link: link.clone(),
+  }];
+
+  for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+    link = format!("{}{}/", link, urlencoding::encode(segment));
+    breadcrumbs.push(Breadcrumb {
+      name: segment.to_string(),
+      link: link.clone(),
+    });
+  }
+
+  breadcrumbs
+}
+
+/// Percent-encode each segment of a path relative to the serve directory
+fn encode_link(path: &Path) -> String {
+  path
+    .components()
+    .map(|component| urlencoding::encode(&component.as_os_str().to_string_lossy()).to_string())
+    .collect::<Vec<String>>()
+    .join("/")
 }
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use globset::GlobBuilder;
+
+use crate::config::PathHeaders;
+
+/// Name of the Netlify-style headers file read from the serve directory
+pub const HEADERS_FILE_NAME: &str = "_headers";
+
+/// Per-path headers from a "_headers" file. The file is checked for
+/// changes on every lookup so edits apply without restarting
+pub struct HeadersFile {
+  path: PathBuf,
+  state: Mutex<HeadersFileState>,
+}
+
+struct HeadersFileState {
+  modified: Option<SystemTime>,
+  rules: Arc<Vec<PathHeaders>>,
+}
+
+impl HeadersFile {
+  pub fn new(serve_dir: &Path) -> anyhow::Result<Self> {
+    let path = serve_dir.join(HEADERS_FILE_NAME);
+    let modified = modified(&path);
+    let rules = match modified {
+      Some(_) => load(&path)?,
+      None => Vec::new(),
+    };
+
+    Ok(Self {
+      path,
+      state: Mutex::new(HeadersFileState {
+        modified,
+        rules: Arc::new(rules),
+      }),
+    })
+  }
+
+  /// Current rules, re-reading the file if it changed since the last call.
+  /// When the new contents are invalid the previous rules are kept
+  pub fn rules(&self) -> (Arc<Vec<PathHeaders>>, Option<anyhow::Error>) {
+    let mut state = self.state.lock().unwrap();
+    let modified = modified(&self.path);
+    if modified == state.modified {
+      return (state.rules.clone(), None);
+    }
+
+    state.modified = modified;
+    let rules = match modified {
+      Some(_) => load(&self.path),
+      None => Ok(Vec::new()),
+    };
+
+    match rules {
+      Ok(rules) => {
+        state.rules = Arc::new(rules);
+        (state.rules.clone(), None)
+      }
+      Err(error) => (state.rules.clone(), Some(error)),
+    }
+  }
+}
+
+/// Headers from every block whose path matches, in file order
+pub fn matching_headers(
+  rules: &[PathHeaders],
+  path: &str,
+) -> HashMap<String, Vec<String>> {
+  let mut headers = HashMap::<String, Vec<String>>::new();
+
+  for rule in rules.iter().filter(|rule| rule.pattern.is_match(path)) {
+    for (key, values) in rule.headers.iter() {
+      headers
+        .entry(key.clone())
+        .or_default()
+        .extend(values.iter().cloned());
+    }
+  }
+
+  headers
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+  fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn load(path: &Path) -> anyhow::Result<Vec<PathHeaders>> {
+  let contents = fs::read_to_string(path)?;
+  match parse_headers_file(&contents) {
+    Ok(rules) => Ok(rules),
+    Err(error) => Err(anyhow::anyhow!(
+      "Invalid headers file {:?} at {}",
+      path,
+      error
+    )),
+  }
+}
+
+/// Parse a Netlify-style "_headers" file. A path pattern on its own line
+/// is followed by indented "Name: value" lines
+///
+/// /assets/*
+///   Cache-Control: public, max-age=31536000, immutable
+pub fn parse_headers_file(contents: &str) -> anyhow::Result<Vec<PathHeaders>> {
+  let mut rules = Vec::<PathHeaders>::new();
+
+  for (i, line) in contents.lines().enumerate() {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+      continue;
+    }
+
+    let is_indented = line.starts_with(char::is_whitespace);
+
+    if !is_indented {
+      if !trimmed.starts_with('/') {
+        return Err(anyhow::anyhow!(
+          "line {}: Expected a path starting with \"/\" or an indented header, got \"{}\"",
+          i + 1,
+          trimmed
+        ));
+      }
+
+      let Ok(glob) = GlobBuilder::new(&path_to_glob(trimmed))
+        .literal_separator(true)
+        .build()
+      else {
+        return Err(anyhow::anyhow!(
+          "line {}: Invalid path \"{}\"",
+          i + 1,
+          trimmed
+        ));
+      };
+
+      rules.push(PathHeaders {
+        pattern: glob.compile_matcher(),
+        headers: HashMap::new(),
+      });
+      continue;
+    }
+
+    let Some(rule) = rules.last_mut() else {
+      return Err(anyhow::anyhow!("line {}: Header without a path", i + 1));
+    };
+
+    let Some((key, value)) = trimmed.split_once(':') else {
+      return Err(anyhow::anyhow!(
+        "line {}: Unable to parse header \"{}\"",
+        i + 1,
+        trimmed
+      ));
+    };
+
+    rule
+      .headers
+      .entry(key.trim().to_lowercase())
+      .or_default()
+      .push(value.trim().to_string());
+  }
+
+  Ok(rules)
+}
+
+/// "*" segments match the rest of the path and ":name" segments match
+/// a single segment, as on Netlify
+fn path_to_glob(path: &str) -> String {
+  path
+    .split('/')
+    .map(|segment| match segment {
+      "*" => "**",
+      segment if segment.starts_with(':') => "*",
+      segment => segment,
+    })
+    .collect::<Vec<&str>>()
+    .join("/")
+}
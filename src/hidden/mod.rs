@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use ignore::gitignore::Gitignore;
+use ignore::gitignore::GitignoreBuilder;
+
+/// Ignore files read from each directory, later files can re-include
+/// paths excluded by earlier ones with "!pattern"
+pub const IGNORE_FILE_NAMES: [&str; 2] = [".gitignore", ".http-server-ignore"];
+
+/// Dotfiles that are still served without --show-hidden
+const ALLOWED_DOTFILES: [&str; 1] = [".well-known"];
+
+/// Paths under the serve directory that are neither listed nor served:
+/// dotfiles (unless shown), "--ignore" globs and patterns from ignore
+/// files. Ignore files are checked for changes on every lookup
+#[derive(Debug)]
+pub struct HiddenFiles {
+  serve_dir: PathBuf,
+  show_hidden: bool,
+  globs: Gitignore,
+  dirs: Mutex<HashMap<PathBuf, IgnoreDir>>,
+}
+
+#[derive(Debug)]
+struct IgnoreDir {
+  modified: [Option<SystemTime>; IGNORE_FILE_NAMES.len()],
+  matcher: Arc<Gitignore>,
+}
+
+impl Default for HiddenFiles {
+  fn default() -> Self {
+    Self {
+      serve_dir: PathBuf::new(),
+      show_hidden: false,
+      globs: Gitignore::empty(),
+      dirs: Mutex::new(HashMap::new()),
+    }
+  }
+}
+
+impl HiddenFiles {
+  /// "globs" use .gitignore syntax and are relative to "serve_dir"
+  pub fn new(
+    serve_dir: &Path,
+    show_hidden: bool,
+    globs: &[String],
+  ) -> anyhow::Result<Self> {
+    let mut builder = GitignoreBuilder::new(serve_dir);
+    for glob in globs {
+      if let Err(error) = builder.add_line(None, glob) {
+        return Err(anyhow::anyhow!(
+          "Invalid ignore glob \"{}\"\n{}",
+          glob,
+          error
+        ));
+      }
+    }
+
+    Ok(Self {
+      serve_dir: serve_dir.to_path_buf(),
+      show_hidden,
+      globs: builder.build()?,
+      dirs: Mutex::new(HashMap::new()),
+    })
+  }
+
+  pub fn show_hidden(&self) -> bool {
+    self.show_hidden
+  }
+
+  /// Whether "path" or any of its parents up to the serve directory is
+  /// hidden. Like git, a path inside an ignored directory can't be
+  /// re-included
+  pub fn is_hidden(
+    &self,
+    path: &Path,
+  ) -> bool {
+    let Ok(relative) = path.strip_prefix(&self.serve_dir) else {
+      return false;
+    };
+
+    let mut current = self.serve_dir.clone();
+    let mut components = relative.components().peekable();
+
+    while let Some(component) = components.next() {
+      let Component::Normal(name) = component else {
+        continue;
+      };
+      current.push(name);
+
+      let name = name.to_string_lossy();
+      if !self.show_hidden && name.starts_with('.') && !ALLOWED_DOTFILES.contains(&name.as_ref()) {
+        return true;
+      }
+
+      let is_dir = components.peek().is_some() || current.is_dir();
+      if self.globs.matched(&current, is_dir).is_ignore() || self.is_ignored(&current, is_dir) {
+        return true;
+      }
+    }
+
+    false
+  }
+
+  /// Check "path" against the ignore files of its parent directories,
+  /// the closest file with a matching pattern decides
+  fn is_ignored(
+    &self,
+    path: &Path,
+    is_dir: bool,
+  ) -> bool {
+    for dir in path.ancestors().skip(1) {
+      let matcher = self.matcher(dir);
+      let matched = matcher.matched(path, is_dir);
+      if matched.is_ignore() {
+        return true;
+      }
+      if matched.is_whitelist() || dir == self.serve_dir {
+        return false;
+      }
+    }
+
+    false
+  }
+
+  /// Patterns from the ignore files in "dir", re-read if they changed
+  fn matcher(
+    &self,
+    dir: &Path,
+  ) -> Arc<Gitignore> {
+    let modified = IGNORE_FILE_NAMES.map(|name| {
+      fs::metadata(dir.join(name))
+        .and_then(|meta| meta.modified())
+        .ok()
+    });
+
+    let mut dirs = self.dirs.lock().unwrap();
+    if let Some(ignore_dir) = dirs.get(dir) {
+      if ignore_dir.modified == modified {
+        return ignore_dir.matcher.clone();
+      }
+    }
+
+    // Invalid lines are skipped, as git does
+    let mut builder = GitignoreBuilder::new(dir);
+    for (name, modified) in IGNORE_FILE_NAMES.iter().zip(modified.iter()) {
+      if modified.is_some() {
+        builder.add(dir.join(name));
+      }
+    }
+    let matcher = Arc::new(builder.build().unwrap_or_else(|_| Gitignore::empty()));
+
+    dirs.insert(
+      dir.to_path_buf(),
+      IgnoreDir {
+        modified,
+        matcher: matcher.clone(),
+      },
+    );
+    matcher
+  }
+}
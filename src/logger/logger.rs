@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use colored::Colorize;
 
+use crate::config::ConfigSource;
+
 #[derive(Default)]
 pub enum Logger {
   Quiet,
@@ -32,19 +34,33 @@ impl Logger {
   pub fn print_folder(
     &self,
     message: &str,
+    source: &ConfigSource,
   ) {
     match self {
       Logger::Quiet => return,
       Logger::Default => {}
     }
     let key = "Directory:".to_string();
-    println!("📁 {:<19} {}", key.bold(), message);
+    println!("📁 {:<19} {}{}", key.bold(), message, format_source(source));
+  }
+
+  pub fn print_config_file(
+    &self,
+    message: &str,
+  ) {
+    match self {
+      Logger::Quiet => return,
+      Logger::Default => {}
+    }
+    let key = "Config File:".to_string();
+    println!("📄 {:<19} {}", key.bold(), message);
   }
 
   pub fn print_config(
     &self,
     key: &str,
     value: &bool,
+    source: &ConfigSource,
   ) {
     match self {
       Logger::Quiet => return,
@@ -52,7 +68,32 @@ impl Logger {
     }
     let message = if *value { "Enabled" } else { "Disabled" };
     let key = format!("{}:", key);
-    println!("🔧 {:<19} {}", key.bold(), message);
+    println!("🔧 {:<19} {}{}", key.bold(), message, format_source(source));
+  }
+
+  pub fn print_link(
+    &self,
+    url: &str,
+    source: &ConfigSource,
+  ) {
+    match self {
+      Logger::Quiet => return,
+      Logger::Default => {}
+    }
+    println!("🔗 {}{}", url, format_source(source));
+  }
+
+  pub fn print_proxy(
+    &self,
+    prefix: &str,
+    upstream: &str,
+  ) {
+    match self {
+      Logger::Quiet => return,
+      Logger::Default => {}
+    }
+    let prefix = format!("{}/*", prefix);
+    println!("🔀 {:<19} {}", prefix.bold(), upstream);
   }
 
   pub fn print_headers(
@@ -78,3 +119,11 @@ impl Logger {
     }
   }
 }
+
+/// Suffix noting where a value came from, omitted for defaults
+fn format_source(source: &ConfigSource) -> String {
+  match source {
+    ConfigSource::Default => "".to_string(),
+    source => format!(" {}", format!("({})", source).dimmed()),
+  }
+}
@@ -1,17 +1,31 @@
 #![deny(unused_crate_dependencies)]
 #![allow(clippy::module_inception)]
 
+mod archive;
 mod auth;
 mod b64;
+mod clean_urls;
 mod cli;
 mod compress;
+mod conditional;
 mod config;
+mod config_file;
+mod cors;
 mod explorer;
-mod http1;
+mod headers_file;
+mod hidden;
 mod logger;
+mod markdown;
+mod precompress;
+mod proxy;
+mod range;
+mod rules;
+mod server;
+mod tls;
 mod utils;
 mod watcher;
 
+use std::io::SeekFrom;
 use std::net::UdpSocket;
 #[cfg(unix)]
 use std::os::unix::fs::MetadataExt;
@@ -21,23 +35,53 @@ use std::path::PathBuf;
 use std::sync::mpsc::channel;
 use std::sync::Arc;
 
+use chrono::DateTime;
+use chrono::Utc;
+use clap::CommandFactory;
+use clap::FromArgMatches;
+use cli::CliCommand;
+use cli::CliSubcommand;
 use colored::Colorize;
+use compress::CompressCache;
+use compress::CompressCacheKey;
+use compress::Encoding;
+use conditional::EtagCache;
+use explorer::read_listing;
 use explorer::reload_script;
 use explorer::render_directory_explorer;
-use http1::http1_server;
-use http1::ResponseBuilderExt;
+use explorer::ListingQuery;
 use logger::Logger;
+use markdown::render_markdown_page;
+use markdown::MARKDOWN_EXTENSIONS;
 use mime_guess;
 use normalize_path::NormalizePath;
+use precompress::precompress;
+use range::RangeRequest;
+use server::http_server;
+use server::shutdown_signal;
+use server::HttpServerOptions;
+use server::ResponseBuilderExt;
 use tokio::fs::File;
 use tokio::io;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncSeekExt;
 use tokio::io::AsyncWriteExt;
+use tokio_util::sync::CancellationToken;
 use watcher::Watcher;
 use watcher::WatcherOptions;
 
+use crate::archive::ArchiveFormat;
+use crate::clean_urls::Resolved;
 use crate::config::Config;
+use crate::cors::CorsPolicy;
+use crate::headers_file::HeadersFile;
+use crate::rules::RuleAction;
+use crate::utils::query::parse_query;
 
 const DEFAULT_CHARSET_SUFFIX: &str = "charset=UTF-8";
+const ALLOWED_METHODS: &str = "GET, HEAD, OPTIONS";
+/// Number of files whose content hash is remembered with --etag-hash
+const ETAG_CACHE_SIZE: usize = 4096;
 
 // copy from https://github.com/egmkang/local_ipaddress/blob/master/src/lib.rs
 // Todo: need all ips use https://crates.io/crates/local-ip-address
@@ -59,7 +103,15 @@ fn get_intranet_ip() -> Option<String> {
 }
 
 async fn main_async() -> anyhow::Result<()> {
-  let config = Arc::new(Config::from_cli()?);
+  let matches = CliCommand::command().get_matches();
+
+  if let Some(CliSubcommand::Precompress(command)) =
+    CliCommand::from_arg_matches(&matches)?.subcommand
+  {
+    return tokio::task::spawn_blocking(move || precompress(command, &Logger::Default)).await?;
+  }
+
+  let config = Arc::new(Config::from_cli(&matches)?);
   let logger: Arc<Logger> = match config.quiet {
     true => Arc::new(Logger::Quiet),
     false => Arc::new(Logger::Default),
@@ -68,32 +120,91 @@ async fn main_async() -> anyhow::Result<()> {
   logger.println("🚀 HTTP Server 🌏".green().bold().to_string());
   logger.br();
 
-  logger.print_folder(&config.serve_dir_fmt);
-  logger.print_config("Directory Listings", &true);
-  logger.print_config("Compress (JIT)", &config.compress);
-  logger.print_config("CORS", &config.cors);
-  logger.print_config("SharedArrayBuffer", &config.sab);
-  logger.print_config("SPA", &config.spa);
-  logger.print_config("Watch", &config.watch);
+  let intranet_domain = get_intranet_ip();
+
+  let tls = match (&config.tls_cert, &config.tls_key) {
+    (Some(cert), Some(key)) => Some(tls::from_pem_files(cert, key)?),
+    _ if config.tls_self_signed => {
+      let mut hosts = vec![
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+      ];
+      if config.address != "0.0.0.0" && config.address != "::" {
+        hosts.push(config.address.clone());
+      }
+      if let Some(intranet_domain) = &intranet_domain {
+        hosts.push(intranet_domain.clone());
+      }
+      Some(tls::self_signed(hosts)?)
+    }
+    _ => None,
+  };
+
+  logger.print_folder(&config.serve_dir_fmt, &config.source("serve_dir"));
+  if let Some(config_file) = &config.config_file {
+    logger.print_config_file(&config_file.to_string_lossy());
+  }
+  logger.print_config(
+    "Directory Listings",
+    &config.dir_listing,
+    &config.source("no_dir_listing"),
+  );
+  logger.print_config(
+    "Show Hidden Files",
+    &config.hidden.show_hidden(),
+    &config.source("show_hidden"),
+  );
+  logger.print_config(
+    "Render Markdown",
+    &config.render_markdown,
+    &config.source("render_markdown"),
+  );
+  logger.print_config(
+    "Compress (JIT)",
+    &config.compress,
+    &config.source("compress"),
+  );
+  logger.print_config("CORS", &config.cors.is_some(), &config.source("cors"));
+  logger.print_config("SharedArrayBuffer", &config.sab, &config.source("sab"));
+  let tls_source = match config.tls_cert {
+    Some(_) => config.source("tls_cert"),
+    None => config.source("tls_self_signed"),
+  };
+  logger.print_config("HTTPS", &tls.is_some(), &tls_source);
+  logger.print_config("SPA", &config.spa, &config.source("spa"));
+  logger.print_config("Watch", &config.watch, &config.source("watch"));
   logger.br();
 
   logger.print_headers(&config.headers);
   logger.br();
 
-  logger.println(format!("🔗 http://{}", config.domain));
+  if !config.proxies.is_empty() {
+    for rule in &config.proxies {
+      logger.print_proxy(&rule.prefix, &rule.upstream.to_string());
+    }
+    logger.br();
+  }
+
+  logger.print_link(
+    &format!("{}://{}", config.protocol, config.domain),
+    &config.source("port"),
+  );
   if config.domain != config.domain_pretty {
-    logger.println(format!("🔗 http://{}", config.domain_pretty));
+    logger.println(format!("🔗 {}://{}", config.protocol, config.domain_pretty));
   }
 
   // print intranet ip domain
   // Todo address bind to local ip 127.0.0.1 skip print?
-  let intranet_domain = get_intranet_ip();
   if intranet_domain.is_some() {
     let Some(intranet_domain_str) = intranet_domain.as_ref() else {
       return Err(anyhow::anyhow!("Unable to get intranet domain str"));
     };
     if intranet_domain_str != &config.domain_pretty && intranet_domain_str != &config.domain {
-      logger.println(format!("🔗 http://{}:{}", intranet_domain_str, config.port));
+      logger.println(format!(
+        "🔗 {}://{}:{}",
+        config.protocol, intranet_domain_str, config.port
+      ));
     }
   }
 
@@ -109,17 +220,90 @@ async fn main_async() -> anyhow::Result<()> {
     false => None,
   };
 
-  http1_server(&config.domain, {
+  let compress_cache = Arc::new(CompressCache::new(config.compress_cache_size));
+  let etag_cache = Arc::new(EtagCache::new(ETAG_CACHE_SIZE));
+  let headers_file = Arc::new(HeadersFile::new(&config.serve_dir_abs)?);
+
+  // Free cached responses for files that changed
+  if let Some(watcher) = &watcher {
+    let mut rx = watcher.subscribe();
+    let compress_cache = compress_cache.clone();
+
+    tokio::task::spawn(async move {
+      while let Some(changes) = rx.recv().await {
+        for changed in changes {
+          compress_cache.invalidate(&changed);
+        }
+      }
+    });
+  }
+
+  let shutdown = CancellationToken::new();
+
+  tokio::task::spawn({
+    let shutdown = shutdown.clone();
+    let logger = logger.clone();
+
+    async move {
+      shutdown_signal().await;
+      // Cancel before logging, printing can panic when stdout is closed
+      shutdown.cancel();
+      logger.println(format!("{} Shutting down", "[EXT]".yellow().bold()));
+    }
+  });
+
+  let options = HttpServerOptions {
+    addr: config.domain.clone(),
+    tls,
+    shutdown: shutdown.clone(),
+    shutdown_timeout: config.shutdown_timeout,
+  };
+
+  http_server(options, {
     let config = config.clone();
     let logger = logger.clone();
     let watcher = watcher.clone();
+    let shutdown = shutdown.clone();
+    let compress_cache = compress_cache.clone();
+    let etag_cache = etag_cache.clone();
+    let headers_file = headers_file.clone();
+    let proxy_client = proxy::client();
 
-    move |req, mut res| {
+    move |mut req, mut res| {
       let config = config.clone();
       let logger = logger.clone();
       let watcher = watcher.clone();
+      let shutdown = shutdown.clone();
+      let compress_cache = compress_cache.clone();
+      let etag_cache = etag_cache.clone();
+      let headers_file = headers_file.clone();
+      let proxy_client = proxy_client.clone();
 
       async move {
+        if let Some(cors) = &config.cors {
+          if CorsPolicy::is_preflight(req.method(), req.headers()) {
+            // Proxied paths accept any method, so allow the one requested
+            let mut allowed_methods = ALLOWED_METHODS;
+            if proxy::find(&config.proxies, req.uri().path()).is_some() {
+              if let Some(method) = req
+                .headers()
+                .get("Access-Control-Request-Method")
+                .and_then(|value| value.to_str().ok())
+              {
+                allowed_methods = method;
+                res = res.header("Vary", "Access-Control-Request-Method");
+              }
+            }
+            return Ok(
+              cors
+                .preflight(res, req.headers(), allowed_methods)
+                .status(hyper::StatusCode::NO_CONTENT)
+                .body_from("")?,
+            );
+          }
+          res = cors.apply(res, req.headers());
+        }
+
         // Basic Auth
         if !config.basic_auth.is_empty() {
           let Some(header) = req.headers().get("authorization") else {
@@ -153,6 +337,114 @@ async fn main_async() -> anyhow::Result<()> {
           }
         }
 
+        // Redirect and rewrite rules from _redirects and the config file
+        let mut status = hyper::StatusCode::OK;
+        let mut is_rewrite = false;
+        let action = rules::resolve(&config.rules, req.uri().path(), req.uri().query(), || {
+          let req_path = req.uri().path().replacen("/", "", 1);
+          let req_path = urlencoding::decode(&req_path).unwrap_or_default();
+          let file_path = config.serve_dir_abs.join(req_path.as_ref());
+          file_path.exists() || config.clean_urls && clean_urls::html_path(&file_path).is_some()
+        });
+        match action {
+          Some(RuleAction::Redirect { location, status }) => {
+            logger.println(format!(
+              "{} {} -> {}",
+              format!("[{}]", status).yellow().bold(),
+              req.uri(),
+              location
+            ));
+            return Ok(
+              res
+                .header("Location", location)
+                .status(status)
+                .body_from("")?,
+            );
+          }
+          Some(RuleAction::Rewrite {
+            uri,
+            status: rewrite_status,
+          }) => {
+            *req.uri_mut() = uri.parse()?;
+            status = hyper::StatusCode::from_u16(rewrite_status)?;
+            is_rewrite = true;
+          }
+          None => {}
+        }
+
+        // Proxied paths go to the upstream with any method
+        if let Some(rule) = proxy::find(&config.proxies, req.uri().path()) {
+          let method = req.method().clone();
+          let uri = req.uri().clone();
+
+          let mut upstream_res =
+            match proxy::forward(&proxy_client, rule, req, &config.protocol).await {
+              Ok(upstream_res) => upstream_res,
+              Err(error) => {
+                logger.println(format!(
+                  "{} {} {} -> {} ({})",
+                  "[502]".red().bold(),
+                  method,
+                  uri,
+                  rule.upstream,
+                  error
+                ));
+                return Ok(res.status(502).body_from("Bad gateway")?);
+              }
+            };
+
+          logger.println(format!(
+            "{} {} {} -> {}",
+            format!("[{}]", upstream_res.status().as_u16())
+              .blue()
+              .bold(),
+            method,
+            uri,
+            rule.upstream
+          ));
+
+          // Keep server-wide headers (CORS etc) unless the upstream set its own
+          if let Some(headers) = res.headers_ref() {
+            let missing = headers
+              .iter()
+              .filter(|(key, _)| !upstream_res.headers().contains_key(*key))
+              .map(|(key, value)| (key.clone(), value.clone()))
+              .collect::<Vec<_>>();
+            for (key, value) in missing {
+              upstream_res.headers_mut().append(key, value);
+            }
+          }
+
+          return Ok(upstream_res);
+        }
+
+        // Only reading is supported for static files
+        match *req.method() {
+          hyper::Method::GET | hyper::Method::HEAD => {}
+          hyper::Method::OPTIONS => {
+            return Ok(
+              res
+                .header("Allow", ALLOWED_METHODS)
+                .status(hyper::StatusCode::NO_CONTENT)
+                .body_from("")?,
+            );
+          }
+          _ => {
+            logger.println(format!(
+              "{} {} {}",
+              "[405]".red().bold(),
+              req.method(),
+              req.uri()
+            ));
+            return Ok(
+              res
+                .header("Allow", ALLOWED_METHODS)
+                .status(hyper::StatusCode::METHOD_NOT_ALLOWED)
+                .body_from("Method not allowed")?,
+            );
+          }
+        }
+
         // Remove the leading slash
         let req_path = req.uri().path().to_string().replacen("/", "", 1);
         let req_path = urlencoding::decode(&req_path)?.to_string();
@@ -190,14 +482,21 @@ async fn main_async() -> anyhow::Result<()> {
               format!("text/event-stream; {}", DEFAULT_CHARSET_SUFFIX),
             )
             .header("Cache-Control", "no-cache")
-            .header("Connection", "keep-alive")
             .status(hyper::StatusCode::OK)
             .body_stream(config.stream_buffer_size)?;
 
           let mut rx = watcher.subscribe();
 
           tokio::task::spawn(async move {
-            while let Some(changes) = rx.recv().await {
+            loop {
+              // End the event stream on shutdown so the connection can drain
+              let changes = tokio::select! {
+                changes = rx.recv() => changes,
+                _ = shutdown.cancelled() => None,
+              };
+              let Some(changes) = changes else {
+                break;
+              };
               let msg = format!(
                 "data:{}\n\n",
                 changes
@@ -222,9 +521,42 @@ async fn main_async() -> anyhow::Result<()> {
           return Ok(res.status(403).body_from("Not allowed")?);
         }
 
-        // Try to serve index.html
-        if file_path.is_dir() && file_path.join("index.html").exists() {
-          file_path = file_path.join("index.html");
+        // Never serve the config file as it may contain credentials, the
+        // _redirects and _headers files which configure the server, nor
+        // dotfiles and ignored files
+        if config.is_hidden(&file_path) {
+          logger.println(format!("{} {}", "[404]".red().bold(), req.uri()));
+          return Ok(res.status(404).body_from("File not found")?);
+        }
+
+        // Clean URLs and trailing slash policy
+        file_path = match clean_urls::resolve(&config, req.uri().path(), file_path, is_rewrite) {
+          Resolved::Path(file_path) => file_path,
+          Resolved::Redirect(mut location) => {
+            if let Some(query) = req.uri().query() {
+              location = format!("{}?{}", location, query);
+            }
+            logger.println(format!(
+              "{} {} -> {}",
+              "[301]".yellow().bold(),
+              req.uri(),
+              location
+            ));
+            return Ok(res.header("Location", location).status(301).body_from("")?);
+          }
+        };
+
+        // Try to serve an index file
+        if file_path.is_dir() {
+          if let Some(index_path) = config.find_index(&file_path) {
+            file_path = index_path;
+          }
+        }
+
+        // A clean URL or index file may have mapped onto a hidden file
+        if config.is_hidden(&file_path) {
+          logger.println(format!("{} {}", "[404]".red().bold(), req.uri()));
+          return Ok(res.status(404).body_from("File not found")?);
         }
 
         // Apply custom headers
@@ -234,9 +566,130 @@ async fn main_async() -> anyhow::Result<()> {
           }
         }
 
+        // Apply headers for matching paths from the config file
+        for path_headers in config.path_headers.iter() {
+          if !path_headers.pattern.is_match(req.uri().path()) {
+            continue;
+          }
+          for (key, values) in path_headers.headers.iter() {
+            // Path specific headers replace global ones with the same name
+            if let Some(headers) = res.headers_mut() {
+              headers.remove(key);
+            }
+            for value in values.iter() {
+              res = res.header(key, value);
+            }
+          }
+        }
+
+        // Apply headers for matching paths from _headers
+        let (headers_rules, headers_error) = headers_file.rules();
+        if let Some(error) = headers_error {
+          logger.println(format!("{} {}", "[ERR]".red().bold(), error));
+        }
+        for (key, values) in headers_file::matching_headers(&headers_rules, req.uri().path()) {
+          if let Some(headers) = res.headers_mut() {
+            headers.remove(&key);
+          }
+          for value in values.iter() {
+            res = res.header(&key, value);
+          }
+        }
+
         // Serve folder structure
-        if file_path.is_dir() {
-          let mut output = render_directory_explorer(&config, &req_path, &file_path)?;
+        if config.dir_listing && file_path.is_dir() {
+          // Download the folder as an archive
+          if let Some(format) = ArchiveFormat::from_query(req.uri().query()) {
+            let file_name = format!(
+              "{}.{}",
+              archive::archive_name(&config, &file_path),
+              format.extension()
+            );
+            logger.println(format!(
+              "{} {}",
+              format!("[{}]", status.as_u16()).green().bold(),
+              req.uri()
+            ));
+
+            // "filename" is a plain ASCII fallback for "filename*"
+            let ascii_name = file_name
+              .chars()
+              .map(|c| match c.is_ascii_graphic() && c != '"' || c == ' ' {
+                true => c,
+                false => '_',
+              })
+              .collect::<String>();
+            let (res, writer) = res
+              .header("Content-Type", format.content_type())
+              .header(
+                "Content-Disposition",
+                format!(
+                  "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+                  ascii_name,
+                  urlencoding::encode(&file_name)
+                ),
+              )
+              .status(status)
+              .body_stream(config.stream_buffer_size)?;
+
+            // HEAD responses have no body, so there's nothing to build
+            if req.method() == hyper::Method::HEAD {
+              return Ok(res);
+            }
+
+            let config = config.clone();
+            let logger = logger.clone();
+            let dir = file_path.clone();
+            tokio::task::spawn(async move {
+              if let Err(error) = archive::write_archive(config, dir, format, writer).await {
+                logger.println(format!("{} {}", "[ERR]".red().bold(), error));
+              }
+            });
+
+            return Ok(res);
+          }
+
+          let listing_query = ListingQuery::from_query(req.uri().query());
+          let listing = read_listing(&config, &req_path, &file_path, listing_query)?;
+          res = res.header("Vary", "Accept");
+
+          // Scripts can ask for the listing as JSON
+          let wants_json = parse_query(req.uri().query())
+            .iter()
+            .any(|(key, value)| key == "format" && value == "json")
+            || req
+              .headers()
+              .get("Accept")
+              .and_then(|value| value.to_str().ok())
+              .is_some_and(|accept| accept.contains("application/json"));
+
+          if wants_json {
+            return Ok(
+              res
+                .header(
+                  "Content-Type",
+                  format!("application/json; {}", DEFAULT_CHARSET_SUFFIX),
+                )
+                .status(status)
+                .body_from(serde_json::to_string_pretty(&listing)?)?,
+            );
+          }
+
+          let mut output = match render_directory_explorer(&config, &file_path, &listing) {
+            Ok(output) => output,
+            Err(error) => {
+              logger.println(format!("{} {}\n{}", "[500]".red().bold(), req.uri(), error));
+              return Ok(
+                res
+                  .header(
+                    "Content-Type",
+                    format!("text/plain; {}", DEFAULT_CHARSET_SUFFIX),
+                  )
+                  .status(500)
+                  .body_from(error.to_string())?,
+              );
+            }
+          };
 
           if config.watch {
             output = format!("{}\n<script>{}</script>", output, reload_script());
@@ -249,27 +702,70 @@ async fn main_async() -> anyhow::Result<()> {
                 "Content-Type",
                 format!("text/html;{}", DEFAULT_CHARSET_SUFFIX),
               )
-              .status(200)
+              .status(status)
               .body_from(output)?,
           );
         }
 
+        // Directories left at this point have no index and listings are
+        // disabled, so they are treated like missing files
+
         // If SPA and file doesn't exist, route to root index
-        if config.spa && !file_path.exists() {
-          file_path = config.serve_dir_abs.join("index.html");
+        if config.spa && !file_path.is_file() {
+          if let Some(index_path) = config.find_index(&config.serve_dir_abs) {
+            file_path = index_path;
+          }
         }
 
         // If not SPA an file doesn't exist, route to 404.html
-        if !config.spa && !file_path.exists() {
+        if !config.spa && !file_path.is_file() {
           file_path = config.serve_dir_abs.join("404.html");
+          status = hyper::StatusCode::NOT_FOUND;
         }
 
         // 404 if no file exists
-        if !file_path.exists() {
+        if !file_path.is_file() {
           logger.println(format!("{} {}", "[404]".red().bold(), req.uri()));
           return Ok(res.status(404).body_from("File not found")?);
         }
 
+        // Serve Markdown files as HTML, "?raw" returns them as they are
+        let is_markdown = file_path.extension().is_some_and(|extension| {
+          MARKDOWN_EXTENSIONS.contains(&extension.to_string_lossy().to_lowercase().as_str())
+        });
+        if config.render_markdown
+          && is_markdown
+          && !parse_query(req.uri().query())
+            .iter()
+            .any(|(key, _)| key == "raw")
+        {
+          let markdown = tokio::fs::read_to_string(&file_path).await?;
+          let title = file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+          let mut output = render_markdown_page(&title, &markdown)?;
+
+          if config.watch && !config.no_watch_inject {
+            output = format!("{}\n<script>{}</script>", output, reload_script());
+          }
+
+          logger.println(format!(
+            "{} {}",
+            format!("[{}]", status.as_u16()).green().bold(),
+            req.uri()
+          ));
+          return Ok(
+            res
+              .header(
+                "Content-Type",
+                format!("text/html; {}", DEFAULT_CHARSET_SUFFIX),
+              )
+              .status(status)
+              .body_from(output)?,
+          );
+        }
+
         // Apply mime type
         let mime = self::mime_guess::from_path(&file_path)
           .first()
@@ -288,45 +784,248 @@ async fn main_async() -> anyhow::Result<()> {
           res = res.header("Content-Type", &content_type);
         }
 
-        // If a .br or .gz file is found next to the target, serve that file
+        let accept_encoding = match req.headers().get("accept-encoding") {
+          Some(header) => Some(header.to_str()?),
+          None => None,
+        };
+
+        // If a .br, .zst or .gz file is found next to the target and the client
+        // accepts that encoding, serve that file
         if !config.compress {
-          let brotli_path = PathBuf::from(format!("{}.br", file_path.to_str().unwrap()));
-          let gzip_path = PathBuf::from(format!("{}.gz", file_path.to_str().unwrap()));
-
-          if brotli_path.exists() {
-            file_path = brotli_path;
-            res = res.header("Content-Encoding", "br");
-          } else if gzip_path.exists() {
-            file_path = gzip_path;
-            res = res.header("Content-Encoding", "gzip");
+          let mut sidecars = Vec::<(Encoding, PathBuf)>::new();
+          for encoding in Encoding::ALL {
+            let Some(extension) = encoding.extension() else {
+              continue;
+            };
+            let sidecar_path =
+              PathBuf::from(format!("{}.{}", file_path.to_str().unwrap(), extension));
+            if sidecar_path.exists() {
+              sidecars.push((encoding, sidecar_path));
+            }
+          }
+
+          if !sidecars.is_empty() {
+            res = res.header("Vary", "Accept-Encoding");
+          }
+
+          let available = sidecars
+            .iter()
+            .map(|(encoding, _)| *encoding)
+            .collect::<Vec<Encoding>>();
+
+          if let Some(encoding) = compress::negotiate(accept_encoding, &available) {
+            if let Some((_, sidecar_path)) = sidecars.into_iter().find(|(e, _)| *e == encoding) {
+              file_path = sidecar_path;
+              res = res.header("Content-Encoding", encoding.as_str());
+            }
           }
         }
 
         let mut file = File::open(&file_path).await?;
+        let metadata = file.metadata().await?;
 
         #[cfg(unix)]
-        let content_length = file.metadata().await?.size();
+        let content_length = metadata.size();
         #[cfg(windows)]
-        let content_length = file.metadata().await?.file_size();
+        let content_length = metadata.file_size();
+
+        let last_modified: DateTime<Utc> = metadata.modified()?.into();
+
+        // Stream file if it's larger than 500kb
+        let is_streamed = !mime.starts_with("text/html") && content_length > 500_000;
+
+        // Byte ranges can only be served when the file is sent as it is on disk
+        let injects_reload =
+          config.watch && !config.no_watch_inject && mime.starts_with("text/html");
+
+        // Encoding used to compress the response on the fly
+        let compressible = config.compress && compress::is_compressible(&mime);
+        let jit_encoding = match compressible {
+          true => compress::negotiate(accept_encoding, &Encoding::ALL),
+          false => None,
+        };
 
-        logger.println(format!("{} {}", "[200]".green().bold(), req.uri()));
+        if compressible {
+          res = res.header("Vary", "Accept-Encoding");
+        }
+
+        let is_raw = !(jit_encoding.is_some() || injects_reload);
+
+        // Validators for conditional requests
+        let mut etag = match config.etag_hash {
+          true => {
+            etag_cache
+              .etag_from_contents(&file_path, content_length, &last_modified)
+              .await?
+          }
+          false => conditional::etag_from_metadata(content_length, &last_modified),
+        };
+        if !is_raw {
+          etag = conditional::weaken(&etag);
+        }
+
+        res = res
+          .header("ETag", &etag)
+          .header("Last-Modified", conditional::http_date(&last_modified));
+
+        // Rewrites with a custom status (e.g. a 404 page) are always sent in full
+        let is_ok = status == hyper::StatusCode::OK;
+
+        if is_ok && conditional::is_not_modified(req.headers(), &etag, &last_modified) {
+          logger.println(format!("{} {}", "[304]".green().bold(), req.uri()));
+          return Ok(res.status(hyper::StatusCode::NOT_MODIFIED).body_from("")?);
+        }
+
+        if is_raw && is_ok {
+          res = res.header("Accept-Ranges", "bytes");
+
+          let mut range_request = match req.headers().get("range") {
+            Some(header) => range::parse_range(header.to_str()?, content_length),
+            None => RangeRequest::Full,
+          };
+
+          if let Some(if_range) = req.headers().get("if-range") {
+            if !range::if_range_matches(if_range.to_str()?, &etag, &last_modified) {
+              range_request = RangeRequest::Full;
+            }
+          }
+
+          match range_request {
+            RangeRequest::Full => {}
+            RangeRequest::Unsatisfiable => {
+              logger.println(format!("{} {}", "[416]".red().bold(), req.uri()));
+              return Ok(
+                res
+                  .header("Content-Range", format!("bytes */{}", content_length))
+                  .status(hyper::StatusCode::RANGE_NOT_SATISFIABLE)
+                  .body_from("Range not satisfiable")?,
+              );
+            }
+            RangeRequest::Partial(ranges) if ranges.len() == 1 => {
+              logger.println(format!("{} {}", "[206]".green().bold(), req.uri()));
+              let range = ranges[0];
+
+              let (res, mut writer) = res
+                .header("Content-Range", range.content_range(content_length))
+                .header("Content-Length", range.len())
+                .status(hyper::StatusCode::PARTIAL_CONTENT)
+                .body_stream(config.stream_buffer_size)?;
+
+              tokio::task::spawn(async move {
+                if file.seek(SeekFrom::Start(range.start)).await.is_err() {
+                  return;
+                }
+                io::copy(&mut file.take(range.len()), &mut writer)
+                  .await
+                  .ok();
+              });
+
+              return Ok(res);
+            }
+            RangeRequest::Partial(ranges) => {
+              logger.println(format!("{} {}", "[206]".green().bold(), req.uri()));
+              let boundary = range::multipart_boundary();
+
+              let parts = ranges
+                .into_iter()
+                .map(|range| {
+                  let header =
+                    range::multipart_part_header(&boundary, &mime, &range, content_length);
+                  (header, range)
+                })
+                .collect::<Vec<_>>();
+
+              let trailer = range::multipart_end(&boundary);
+              let body_length = parts
+                .iter()
+                .map(|(header, range)| header.len() as u64 + range.len())
+                .sum::<u64>()
+                + trailer.len() as u64;
+
+              if let Some(headers) = res.headers_mut() {
+                headers.remove("Content-Type");
+              }
+
+              let (res, mut writer) = res
+                .header(
+                  "Content-Type",
+                  format!("multipart/byteranges; boundary={}", boundary),
+                )
+                .header("Content-Length", body_length)
+                .status(hyper::StatusCode::PARTIAL_CONTENT)
+                .body_stream(config.stream_buffer_size)?;
+
+              tokio::task::spawn(async move {
+                for (header, range) in parts {
+                  if writer.write_all(header.as_bytes()).await.is_err() {
+                    return;
+                  }
+                  if file.seek(SeekFrom::Start(range.start)).await.is_err() {
+                    return;
+                  }
+                  if io::copy(&mut (&mut file).take(range.len()), &mut writer)
+                    .await
+                    .is_err()
+                  {
+                    return;
+                  }
+                }
+                writer.write_all(trailer.as_bytes()).await.ok();
+              });
+
+              return Ok(res);
+            }
+          }
+        }
+
+        logger.println(format!(
+          "{} {}",
+          format!("[{}]", status.as_u16()).green().bold(),
+          req.uri()
+        ));
 
         // Read file
-        // Stream file if it's larger than 5mb
-        if !mime.starts_with("text/html") && content_length > 500_000 {
-          let (res, mut writer) = res
-            .header("Connection", "keep-alive")
-            .header("Content-Length", content_length)
-            .status(hyper::StatusCode::OK)
-            .body_stream(config.stream_buffer_size)?;
+        if is_streamed {
+          let (res, mut writer) = match jit_encoding {
+            // The compressed length isn't known upfront so the body is chunked
+            Some(encoding) => res.header("Content-Encoding", encoding.as_str()),
+            None => res.header("Content-Length", content_length),
+          }
+          .status(status)
+          .body_stream(config.stream_buffer_size)?;
 
+          let compression_levels = config.compression_levels;
           tokio::task::spawn(async move {
-            io::copy(&mut file, &mut writer).await.ok();
+            match jit_encoding {
+              Some(encoding) => {
+                compress::compress_stream(file, writer, encoding, compression_levels)
+                  .await
+                  .ok();
+              }
+              None => {
+                io::copy(&mut file, &mut writer).await.ok();
+              }
+            }
           });
 
           return Ok(res);
         }
 
+        let cache_key = jit_encoding.map(|encoding| CompressCacheKey {
+          path: file_path.normalize(),
+          encoding,
+          modified: last_modified.timestamp_millis(),
+          content_length,
+        });
+
+        // Serve a previously compressed response
+        if let Some(cache_key) = &cache_key {
+          if let Some(cached) = compress_cache.get(cache_key) {
+            res = res.header("Content-Encoding", cache_key.encoding.as_str());
+            return Ok(res.status(status).body_from(cached.as_slice())?);
+          }
+        }
+
         let Ok(mut contents) = tokio::fs::read(&file_path).await else {
           return Ok(res.status(500).body_from("Unable to open file")?);
         };
@@ -365,11 +1064,21 @@ async fn main_async() -> anyhow::Result<()> {
           }
         }
 
-        if config.compress {
-          res = res.header("Content-Encoding", "br");
-          Ok(res.status(200).body_from(compress::brotli(&contents))?)
-        } else {
-          Ok(res.status(200).body_from(contents)?)
+        match cache_key {
+          Some(cache_key) => {
+            res = res.header("Content-Encoding", cache_key.encoding.as_str());
+            let compression_levels = config.compression_levels;
+            let encoding = cache_key.encoding;
+            let contents = Arc::new(
+              tokio::task::spawn_blocking(move || {
+                compress::compress(&contents, encoding, &compression_levels)
+              })
+              .await?,
+            );
+            compress_cache.insert(cache_key, contents.clone());
+            Ok(res.status(status).body_from(contents.as_slice())?)
+          }
+          None => Ok(res.status(status).body_from(contents)?),
         }
       }
     }
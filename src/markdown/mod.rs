@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use handlebars::Handlebars;
+use pulldown_cmark::html;
+use pulldown_cmark::CowStr;
+use pulldown_cmark::Event;
+use pulldown_cmark::Options;
+use pulldown_cmark::Parser;
+use pulldown_cmark::Tag;
+use pulldown_cmark::TagEnd;
+use serde_json::json;
+
+const MARKDOWN_PAGE: &str = include_str!("./page.hbs");
+
+/// Markdown file extensions served as HTML with --render-markdown
+pub const MARKDOWN_EXTENSIONS: [&str; 2] = ["md", "markdown"];
+
+/// Render GitHub flavoured Markdown (tables, task lists, strikethrough,
+/// footnotes) to an HTML fragment. Headings get GitHub style ids and an
+/// anchor link so sections can be linked to
+pub fn render_markdown(markdown: &str) -> String {
+  let options = Options::ENABLE_TABLES
+    | Options::ENABLE_FOOTNOTES
+    | Options::ENABLE_STRIKETHROUGH
+    | Options::ENABLE_TASKLISTS
+    | Options::ENABLE_GFM;
+
+  let mut events = Vec::<Event>::new();
+  let mut heading = None::<Vec<Event>>;
+  let mut slugs = HashMap::<String, usize>::new();
+
+  for event in Parser::new_ext(markdown, options) {
+    match event {
+      Event::Start(Tag::Heading { .. }) => heading = Some(vec![event]),
+      Event::End(TagEnd::Heading(_)) => {
+        let Some(mut heading_events) = heading.take() else {
+          continue;
+        };
+
+        let text = heading_events
+          .iter()
+          .filter_map(|event| match event {
+            Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+            _ => None,
+          })
+          .collect::<String>();
+        let slug = unique_slug(&mut slugs, &text);
+
+        if let Some(Event::Start(Tag::Heading { id, .. })) = heading_events.first_mut() {
+          id.get_or_insert(CowStr::from(slug.clone()));
+        }
+        heading_events.insert(
+          1,
+          Event::InlineHtml(CowStr::from(format!(
+            "<a class=\"anchor\" href=\"#{}\" aria-hidden=\"true\">#</a>",
+            slug
+          ))),
+        );
+
+        events.extend(heading_events);
+        events.push(event);
+      }
+      event => match heading.as_mut() {
+        Some(heading_events) => heading_events.push(event),
+        None => events.push(event),
+      },
+    }
+  }
+
+  let mut output = String::new();
+  html::push_html(&mut output, events.into_iter());
+  output
+}
+
+/// A standalone, styled HTML page for a Markdown file
+pub fn render_markdown_page(
+  title: &str,
+  markdown: &str,
+) -> anyhow::Result<String> {
+  let handlebars = Handlebars::new();
+  let output = handlebars.render_template(
+    MARKDOWN_PAGE,
+    &json!({
+      "title": title,
+      "content": render_markdown(markdown),
+    }),
+  )?;
+  Ok(output)
+}
+
+/// Lowercase the heading text, drop punctuation and turn spaces into
+/// dashes. Repeated headings get "-1", "-2" suffixes
+fn unique_slug(
+  slugs: &mut HashMap<String, usize>,
+  text: &str,
+) -> String {
+  let slug = text
+    .trim()
+    .to_lowercase()
+    .chars()
+    .filter_map(|c| match c {
+      ' ' | '-' => Some('-'),
+      c if c.is_alphanumeric() || c == '_' => Some(c),
+      _ => None,
+    })
+    .collect::<String>();
+
+  let count = slugs.entry(slug.clone()).or_insert(0);
+  let unique = match *count {
+    0 => slug,
+    count => format!("{}-{}", slug, count),
+  };
+  *count += 1;
+  unique
+}
@@ -0,0 +1,267 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use colored::Colorize;
+use normalize_path::NormalizePath;
+
+use crate::cli::PrecompressCommand;
+use crate::compress;
+use crate::compress::CompressionLevels;
+use crate::compress::Encoding;
+use crate::logger::Logger;
+
+#[derive(Default)]
+struct Totals {
+  files: usize,
+  written: usize,
+  skipped: usize,
+  original_size: u64,
+  compressed_size: u64,
+}
+
+/// Walk a directory and write precompressed sidecar files next to every
+/// compressible file so they can be served without JIT compression
+pub fn precompress(
+  command: PrecompressCommand,
+  logger: &Logger,
+) -> anyhow::Result<()> {
+  let root = std::env::current_dir()?.join(&command.dir).normalize();
+  if !root.is_dir() {
+    return Err(anyhow::anyhow!("Directory not found {:?}", command.dir));
+  }
+
+  let encodings = command
+    .encodings
+    .iter()
+    .filter_map(|token| {
+      Encoding::ALL
+        .into_iter()
+        .find(|encoding| encoding.as_str() == token)
+    })
+    .collect::<Vec<Encoding>>();
+
+  let levels = CompressionLevels {
+    brotli: command.brotli_level,
+    gzip: command.gzip_level,
+    zstd: command.zstd_level,
+  };
+
+  let mut files = Vec::<PathBuf>::new();
+  collect_files(&root, command.min_size, &mut files)?;
+
+  let queue = Mutex::new(files.into_iter());
+  let totals = Mutex::new(Totals::default());
+
+  // Compress files in parallel, brotli at high levels is slow
+  std::thread::scope(|scope| {
+    for _ in 0..num_cpus::get() {
+      scope.spawn(|| loop {
+        let Some(file_path) = queue.lock().unwrap().next() else {
+          break;
+        };
+
+        let display_path = pathdiff::diff_paths(&file_path, &root).unwrap_or(file_path.clone());
+
+        match precompress_file(&file_path, &encodings, &levels, &command) {
+          Ok(results) => {
+            let mut totals = totals.lock().unwrap();
+            totals.files += 1;
+
+            for result in results {
+              match result {
+                FileResult::Written {
+                  encoding,
+                  original_size,
+                  compressed_size,
+                } => {
+                  totals.written += 1;
+                  totals.original_size += original_size;
+                  totals.compressed_size += compressed_size;
+                  logger.println(format!(
+                    "{} {}.{} {} -> {} bytes ({})",
+                    "[CMP]".green().bold(),
+                    display_path.display(),
+                    encoding.extension().unwrap_or_default(),
+                    original_size,
+                    compressed_size,
+                    format_savings(original_size, compressed_size),
+                  ));
+                }
+                FileResult::Skipped { encoding, reason } => {
+                  totals.skipped += 1;
+                  logger.println(format!(
+                    "{} {}.{} {}",
+                    "[SKP]".yellow().bold(),
+                    display_path.display(),
+                    encoding.extension().unwrap_or_default(),
+                    reason
+                  ));
+                }
+              }
+            }
+          }
+          Err(error) => {
+            logger.println(format!(
+              "{} {} {}",
+              "[ERR]".red().bold(),
+              display_path.display(),
+              error
+            ));
+          }
+        }
+      });
+    }
+  });
+
+  let totals = totals.into_inner().unwrap();
+
+  logger.br();
+  logger.println(format!(
+    "📦 {} files checked, {} written, {} skipped",
+    totals.files, totals.written, totals.skipped
+  ));
+  if totals.written > 0 {
+    logger.println(format!(
+      "📦 {} -> {} bytes ({})",
+      totals.original_size,
+      totals.compressed_size,
+      format_savings(totals.original_size, totals.compressed_size)
+    ));
+  }
+
+  Ok(())
+}
+
+enum FileResult {
+  Written {
+    encoding: Encoding,
+    original_size: u64,
+    compressed_size: u64,
+  },
+  Skipped {
+    encoding: Encoding,
+    reason: &'static str,
+  },
+}
+
+fn precompress_file(
+  file_path: &Path,
+  encodings: &[Encoding],
+  levels: &CompressionLevels,
+  command: &PrecompressCommand,
+) -> anyhow::Result<Vec<FileResult>> {
+  let metadata = fs::metadata(file_path)?;
+  let modified = metadata.modified()?;
+  let mut contents = None::<Vec<u8>>;
+  let mut results = Vec::<FileResult>::new();
+
+  for encoding in encodings {
+    let Some(extension) = encoding.extension() else {
+      continue;
+    };
+    let mut sidecar_path = file_path.as_os_str().to_owned();
+    sidecar_path.push(".");
+    sidecar_path.push(extension);
+    let sidecar_path = PathBuf::from(sidecar_path);
+
+    // Sidecar is already up to date
+    if !command.force {
+      if let Ok(sidecar_modified) = fs::metadata(&sidecar_path).and_then(|meta| meta.modified()) {
+        if sidecar_modified >= modified {
+          results.push(FileResult::Skipped {
+            encoding: *encoding,
+            reason: "up to date",
+          });
+          continue;
+        }
+      }
+    }
+
+    if contents.is_none() {
+      contents = Some(fs::read(file_path)?);
+    }
+    let original = contents.as_deref().unwrap_or_default();
+
+    let compressed = compress::compress(original, *encoding, levels);
+    let original_size = original.len() as u64;
+    let compressed_size = compressed.len() as u64;
+
+    // Not worth serving a compressed copy, remove any stale one
+    if compressed_size * 100 > original_size * (100 - command.min_savings as u64) {
+      if sidecar_path.exists() {
+        fs::remove_file(&sidecar_path)?;
+      }
+      results.push(FileResult::Skipped {
+        encoding: *encoding,
+        reason: "doesn't compress well",
+      });
+      continue;
+    }
+
+    fs::write(&sidecar_path, compressed)?;
+    results.push(FileResult::Written {
+      encoding: *encoding,
+      original_size,
+      compressed_size,
+    });
+  }
+
+  Ok(results)
+}
+
+/// Recursively collect compressible files, ignoring existing sidecars
+/// and files too small to benefit from compression
+fn collect_files(
+  dir: &Path,
+  min_size: u64,
+  files: &mut Vec<PathBuf>,
+) -> anyhow::Result<()> {
+  for item in fs::read_dir(dir)? {
+    let item = item?;
+    let file_type = item.file_type()?;
+    let path = item.path();
+
+    if file_type.is_dir() {
+      collect_files(&path, min_size, files)?;
+      continue;
+    }
+
+    if !file_type.is_file() || item.metadata()?.len() < min_size {
+      continue;
+    }
+
+    let is_sidecar = path.extension().is_some_and(|ext| {
+      Encoding::ALL
+        .iter()
+        .any(|encoding| encoding.extension().is_some_and(|sidecar| ext == sidecar))
+    });
+    if is_sidecar {
+      continue;
+    }
+
+    let mime = mime_guess::from_path(&path)
+      .first()
+      .map(|v| v.to_string())
+      .unwrap_or_default();
+    if !compress::is_compressible(&mime) {
+      continue;
+    }
+
+    files.push(path);
+  }
+
+  Ok(())
+}
+
+fn format_savings(
+  original_size: u64,
+  compressed_size: u64,
+) -> String {
+  if original_size == 0 {
+    return "0%".to_string();
+  }
+  let saved = 100.0 - (compressed_size as f64 / original_size as f64 * 100.0);
+  format!("-{:.1}%", saved)
+}
@@ -0,0 +1,241 @@
+use std::convert::Infallible;
+
+use futures::future;
+use futures::StreamExt;
+use http_body_util::combinators::BoxBody;
+use http_body_util::BodyStream;
+use http_body_util::StreamBody;
+use hyper::body::Bytes as HyperBytes;
+use hyper::body::Incoming;
+use hyper::header::HeaderMap;
+use hyper::header::HeaderName;
+use hyper::header::HeaderValue;
+use hyper::header::CONNECTION;
+use hyper::header::HOST;
+use hyper::header::PROXY_AUTHENTICATE;
+use hyper::header::PROXY_AUTHORIZATION;
+use hyper::header::TE;
+use hyper::header::TRAILER;
+use hyper::header::TRANSFER_ENCODING;
+use hyper::header::UPGRADE;
+use hyper::Request;
+use hyper::Response;
+use hyper::StatusCode;
+use hyper::Uri;
+use hyper::Version;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use hyper_util::rt::TokioIo;
+
+pub type ProxyClient = Client<HttpConnector, Incoming>;
+
+/// Headers that only apply to a single connection and must not be forwarded
+const HOP_BY_HOP: [HeaderName; 7] = [
+  CONNECTION,
+  HeaderName::from_static("keep-alive"),
+  PROXY_AUTHENTICATE,
+  PROXY_AUTHORIZATION,
+  TE,
+  TRAILER,
+  TRANSFER_ENCODING,
+];
+
+pub fn client() -> ProxyClient {
+  Client::builder(TokioExecutor::new()).build_http()
+}
+
+/// Requests whose path starts with "prefix" are forwarded to "upstream"
+#[derive(Debug, Clone)]
+pub struct ProxyRule {
+  pub prefix: String,
+  pub upstream: Uri,
+}
+
+impl ProxyRule {
+  /// Parse a rule in the form "/prefix=http://host:port"
+  pub fn parse(rule: &str) -> anyhow::Result<Self> {
+    let Some((prefix, upstream)) = rule.split_once("=") else {
+      return Err(anyhow::anyhow!("Unable to parse proxy \"{}\"", rule));
+    };
+    Self::new(prefix, upstream)
+  }
+
+  pub fn new(
+    prefix: &str,
+    upstream: &str,
+  ) -> anyhow::Result<Self> {
+    if !prefix.starts_with('/') {
+      return Err(anyhow::anyhow!(
+        "Proxy path \"{}\" must start with \"/\"",
+        prefix
+      ));
+    }
+
+    let Ok(upstream_uri) = upstream.parse::<Uri>() else {
+      return Err(anyhow::anyhow!(
+        "Unable to parse proxy target \"{}\"",
+        upstream
+      ));
+    };
+
+    if upstream_uri.scheme_str() != Some("http") || upstream_uri.authority().is_none() {
+      return Err(anyhow::anyhow!(
+        "Proxy target \"{}\" must be an http:// URL",
+        upstream
+      ));
+    }
+
+    Ok(Self {
+      prefix: prefix.trim_end_matches('/').to_string(),
+      upstream: upstream_uri,
+    })
+  }
+
+  /// Matches whole path segments so "/api" doesn't match "/apis"
+  pub fn matches(
+    &self,
+    path: &str,
+  ) -> bool {
+    match path.strip_prefix(&self.prefix) {
+      Some(rest) => rest.is_empty() || rest.starts_with('/'),
+      None => false,
+    }
+  }
+
+  /// The request path is appended to the upstream as is, unless the
+  /// upstream has a path of its own which then replaces the prefix
+  fn target(
+    &self,
+    uri: &Uri,
+  ) -> anyhow::Result<Uri> {
+    let path_and_query = uri.path_and_query().map(|v| v.as_str()).unwrap_or("/");
+    let upstream_path = self.upstream.path().trim_end_matches('/');
+
+    let rest = match upstream_path.is_empty() {
+      true => path_and_query,
+      false => &path_and_query[self.prefix.len()..],
+    };
+
+    let Some(authority) = self.upstream.authority() else {
+      return Err(anyhow::anyhow!("Proxy target has no host"));
+    };
+
+    let target = format!("http://{}{}{}", authority, upstream_path, rest);
+    Ok(target.parse::<Uri>()?)
+  }
+}
+
+/// Most specific rule matching a request path
+pub fn find<'a>(
+  rules: &'a [ProxyRule],
+  path: &str,
+) -> Option<&'a ProxyRule> {
+  rules
+    .iter()
+    .filter(|rule| rule.matches(path))
+    .max_by_key(|rule| rule.prefix.len())
+}
+
+/// Forward a request to the upstream of "rule" and stream the response back.
+/// Protocol upgrades (WebSockets) are spliced together once both sides agree
+pub async fn forward(
+  client: &ProxyClient,
+  rule: &ProxyRule,
+  mut req: Request<Incoming>,
+  protocol: &str,
+) -> anyhow::Result<Response<BoxBody<HyperBytes, Infallible>>> {
+  let target = rule.target(req.uri())?;
+  let is_upgrade = req.headers().contains_key(UPGRADE);
+  let client_upgrade = is_upgrade.then(|| hyper::upgrade::on(&mut req));
+
+  let (mut parts, body) = req.into_parts();
+
+  let host = match parts.headers.get(HOST) {
+    Some(host) => Some(host.clone()),
+    None => parts
+      .uri
+      .authority()
+      .and_then(|authority| HeaderValue::from_str(authority.as_str()).ok()),
+  };
+
+  strip_hop_by_hop(&mut parts.headers, is_upgrade);
+  if let Some(host) = host {
+    parts.headers.insert("X-Forwarded-Host", host);
+  }
+  parts
+    .headers
+    .insert("X-Forwarded-Proto", HeaderValue::from_str(protocol)?);
+  if let Some(authority) = target.authority() {
+    parts
+      .headers
+      .insert(HOST, HeaderValue::from_str(authority.as_str())?);
+  }
+  parts.uri = target;
+  parts.version = Version::HTTP_11;
+
+  let mut upstream_res = client.request(Request::from_parts(parts, body)).await?;
+
+  if upstream_res.status() == StatusCode::SWITCHING_PROTOCOLS {
+    if let Some(client_upgrade) = client_upgrade {
+      let upstream_upgrade = hyper::upgrade::on(&mut upstream_res);
+      tokio::spawn(async move {
+        let (Ok(client_io), Ok(upstream_io)) = tokio::join!(client_upgrade, upstream_upgrade)
+        else {
+          return;
+        };
+        let mut client_io = TokioIo::new(client_io);
+        let mut upstream_io = TokioIo::new(upstream_io);
+        tokio::io::copy_bidirectional(&mut client_io, &mut upstream_io)
+          .await
+          .ok();
+      });
+    }
+  } else {
+    strip_hop_by_hop(upstream_res.headers_mut(), false);
+  }
+
+  let (mut parts, body) = upstream_res.into_parts();
+
+  // The client connection keeps its own protocol version, an HTTP/1.0
+  // upstream would otherwise turn off keep-alive
+  parts.version = Version::default();
+
+  // An upstream error mid-body can only be reported by ending the stream early
+  let stream = BodyStream::new(body)
+    .take_while(|frame| future::ready(frame.is_ok()))
+    .filter_map(|frame| future::ready(frame.ok().map(Ok::<_, Infallible>)));
+
+  Ok(Response::from_parts(
+    parts,
+    BoxBody::new(StreamBody::new(stream)),
+  ))
+}
+
+fn strip_hop_by_hop(
+  headers: &mut HeaderMap,
+  keep_upgrade: bool,
+) {
+  let listed = headers
+    .get_all(CONNECTION)
+    .iter()
+    .filter_map(|value| value.to_str().ok())
+    .flat_map(|value| value.split(','))
+    .map(|name| name.trim().to_lowercase())
+    .filter(|name| !(keep_upgrade && name == "upgrade"))
+    .collect::<Vec<String>>();
+
+  for name in listed {
+    headers.remove(name.as_str());
+  }
+
+  for name in HOP_BY_HOP {
+    if keep_upgrade && name == CONNECTION {
+      continue;
+    }
+    headers.remove(name);
+  }
+  if !keep_upgrade {
+    headers.remove(UPGRADE);
+  }
+}
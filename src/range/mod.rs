@@ -0,0 +1,319 @@
+use chrono::DateTime;
+use chrono::Utc;
+
+use crate::conditional;
+
+/// Inclusive byte range within a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteRange {
+  pub start: u64,
+  pub end: u64,
+}
+
+impl ByteRange {
+  pub fn len(&self) -> u64 {
+    self.end - self.start + 1
+  }
+
+  pub fn content_range(
+    &self,
+    total: u64,
+  ) -> String {
+    format!("bytes {}-{}/{}", self.start, self.end, total)
+  }
+}
+
+#[derive(Debug)]
+pub enum RangeRequest {
+  /// No usable Range header, serve the whole file
+  Full,
+  /// One or more satisfiable ranges
+  Partial(Vec<ByteRange>),
+  /// Valid Range header but no range overlaps the file
+  Unsatisfiable,
+}
+
+/// Maximum number of ranges accepted in a single request. Requests asking
+/// for more are served in full rather than as a huge multipart body
+const MAX_RANGES: usize = 64;
+
+/// Parse a "Range" header value against a file of "total" bytes
+pub fn parse_range(
+  header: &str,
+  total: u64,
+) -> RangeRequest {
+  let Some(specs) = header.trim().strip_prefix("bytes=") else {
+    return RangeRequest::Full;
+  };
+
+  let mut ranges = Vec::<ByteRange>::new();
+
+  for spec in specs.split(',') {
+    let Some((start, end)) = spec.trim().split_once('-') else {
+      return RangeRequest::Full;
+    };
+
+    let start = start.trim();
+    let end = end.trim();
+
+    // Suffix range "-500" is the last 500 bytes
+    if start.is_empty() {
+      let Ok(suffix) = end.parse::<u64>() else {
+        return RangeRequest::Full;
+      };
+      if suffix == 0 || total == 0 {
+        continue;
+      }
+      ranges.push(ByteRange {
+        start: total.saturating_sub(suffix),
+        end: total - 1,
+      });
+      continue;
+    }
+
+    let Ok(start) = start.parse::<u64>() else {
+      return RangeRequest::Full;
+    };
+
+    let end = match end.is_empty() {
+      true => u64::MAX,
+      false => {
+        let Ok(end) = end.parse::<u64>() else {
+          return RangeRequest::Full;
+        };
+        if end < start {
+          return RangeRequest::Full;
+        }
+        end
+      }
+    };
+
+    if start >= total {
+      continue;
+    }
+
+    ranges.push(ByteRange {
+      start,
+      end: end.min(total - 1),
+    });
+  }
+
+  if ranges.is_empty() {
+    return RangeRequest::Unsatisfiable;
+  }
+
+  let ranges = coalesce(ranges);
+  if ranges.len() > MAX_RANGES {
+    return RangeRequest::Full;
+  }
+
+  RangeRequest::Partial(ranges)
+}
+
+/// Merge overlapping and adjacent ranges so no byte is sent twice, as
+/// allowed by RFC 9110 section 14.2. The result is sorted by offset
+fn coalesce(mut ranges: Vec<ByteRange>) -> Vec<ByteRange> {
+  ranges.sort_by_key(|range| range.start);
+
+  let mut merged = Vec::<ByteRange>::with_capacity(ranges.len());
+  for range in ranges {
+    match merged.last_mut() {
+      Some(last) if range.start <= last.end.saturating_add(1) => {
+        last.end = last.end.max(range.end);
+      }
+      _ => merged.push(range),
+    }
+  }
+  merged
+}
+
+/// Check an "If-Range" header against the current validators of the file.
+/// When it doesn't match, the Range header must be ignored
+pub fn if_range_matches(
+  header: &str,
+  etag: &str,
+  last_modified: &DateTime<Utc>,
+) -> bool {
+  let header = header.trim();
+
+  // Entity tags use the strong comparison, weak tags never match
+  if header.starts_with('"') || header.starts_with("W/") {
+    return !conditional::is_weak(header) && !conditional::is_weak(etag) && header == etag;
+  }
+
+  let Some(date) = conditional::parse_http_date(header) else {
+    return false;
+  };
+
+  date.timestamp() == last_modified.timestamp()
+}
+
+/// Header written before each part of a multipart/byteranges body
+pub fn multipart_part_header(
+  boundary: &str,
+  content_type: &str,
+  range: &ByteRange,
+  total: u64,
+) -> String {
+  let mut header = format!("\r\n--{}\r\n", boundary);
+  if !content_type.is_empty() {
+    header.push_str(&format!("Content-Type: {}\r\n", content_type));
+  }
+  header.push_str(&format!(
+    "Content-Range: {}\r\n\r\n",
+    range.content_range(total)
+  ));
+  header
+}
+
+/// Trailer written after the last part of a multipart/byteranges body
+pub fn multipart_end(boundary: &str) -> String {
+  format!("\r\n--{}--\r\n", boundary)
+}
+
+/// Boundary string separating the parts of a multipart/byteranges body
+pub fn multipart_boundary() -> String {
+  format!(
+    "http-server-rs-{:x}",
+    Utc::now().timestamp_nanos_opt().unwrap_or_default()
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::TimeZone;
+
+  use super::*;
+
+  fn partial(ranges: &[(u64, u64)]) -> Vec<ByteRange> {
+    ranges
+      .iter()
+      .map(|&(start, end)| ByteRange { start, end })
+      .collect()
+  }
+
+  fn parse(header: &str) -> Option<Vec<ByteRange>> {
+    match parse_range(header, 1000) {
+      RangeRequest::Partial(ranges) => Some(ranges),
+      _ => None,
+    }
+  }
+
+  #[test]
+  fn closed_range() {
+    assert_eq!(parse("bytes=0-499"), Some(partial(&[(0, 499)])));
+    assert_eq!(parse("bytes= 10 - 20 "), Some(partial(&[(10, 20)])));
+  }
+
+  #[test]
+  fn open_ended_range() {
+    assert_eq!(parse("bytes=900-"), Some(partial(&[(900, 999)])));
+  }
+
+  #[test]
+  fn suffix_range() {
+    assert_eq!(parse("bytes=-100"), Some(partial(&[(900, 999)])));
+    assert_eq!(parse("bytes=-5000"), Some(partial(&[(0, 999)])));
+  }
+
+  #[test]
+  fn end_past_the_end_is_clamped() {
+    assert_eq!(parse("bytes=500-5000"), Some(partial(&[(500, 999)])));
+  }
+
+  #[test]
+  fn unsatisfiable() {
+    assert!(matches!(
+      parse_range("bytes=1000-", 1000),
+      RangeRequest::Unsatisfiable
+    ));
+    assert!(matches!(
+      parse_range("bytes=-0", 1000),
+      RangeRequest::Unsatisfiable
+    ));
+    assert!(matches!(
+      parse_range("bytes=0-", 0),
+      RangeRequest::Unsatisfiable
+    ));
+  }
+
+  #[test]
+  fn unsatisfiable_specs_are_dropped() {
+    assert_eq!(parse("bytes=2000-3000,0-9"), Some(partial(&[(0, 9)])));
+  }
+
+  #[test]
+  fn malformed_header_serves_full_file() {
+    for header in [
+      "bytes=abc",
+      "bytes=5",
+      "bytes=10-5",
+      "bytes=-",
+      "bytes=1-x",
+      "items=0-10",
+      "",
+    ] {
+      assert!(
+        matches!(parse_range(header, 1000), RangeRequest::Full),
+        "{}",
+        header
+      );
+    }
+  }
+
+  #[test]
+  fn overlapping_ranges_are_merged() {
+    assert_eq!(parse("bytes=0-,0-,0-"), Some(partial(&[(0, 999)])));
+    assert_eq!(parse("bytes=0-99,50-149"), Some(partial(&[(0, 149)])));
+    assert_eq!(parse("bytes=100-199,0-99"), Some(partial(&[(0, 199)])));
+    assert_eq!(
+      parse("bytes=500-599,0-9,-100"),
+      Some(partial(&[(0, 9), (500, 599), (900, 999)]))
+    );
+  }
+
+  #[test]
+  fn too_many_ranges_serves_full_file() {
+    let specs = (0..=MAX_RANGES as u64)
+      .map(|i| format!("{}-{}", i * 10, i * 10 + 1))
+      .collect::<Vec<String>>()
+      .join(",");
+    assert!(matches!(
+      parse_range(&format!("bytes={}", specs), 10_000),
+      RangeRequest::Full
+    ));
+  }
+
+  fn modified() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+  }
+
+  #[test]
+  fn if_range_with_strong_etag() {
+    assert!(if_range_matches("\"abc\"", "\"abc\"", &modified()));
+    assert!(!if_range_matches("\"abd\"", "\"abc\"", &modified()));
+  }
+
+  #[test]
+  fn if_range_with_weak_etag_never_matches() {
+    assert!(!if_range_matches("W/\"abc\"", "W/\"abc\"", &modified()));
+    assert!(!if_range_matches("W/\"abc\"", "\"abc\"", &modified()));
+    assert!(!if_range_matches("\"abc\"", "W/\"abc\"", &modified()));
+  }
+
+  #[test]
+  fn if_range_with_date() {
+    let etag = "W/\"abc\"";
+    assert!(if_range_matches(
+      "Wed, 01 May 2024 12:00:00 GMT",
+      etag,
+      &modified()
+    ));
+    assert!(!if_range_matches(
+      "Thu, 02 May 2024 12:00:00 GMT",
+      etag,
+      &modified()
+    ));
+    assert!(!if_range_matches("not a date", etag, &modified()));
+  }
+}
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+
+use crate::utils::query::parse_query;
+
+/// Name of the Netlify-style rules file read from the serve directory
+pub const REDIRECTS_FILE_NAME: &str = "_redirects";
+
+/// A redirect or rewrite rule. Rules are evaluated in order and the
+/// first matching rule wins
+#[derive(Debug, Clone)]
+pub struct Rule {
+  from: Vec<Segment>,
+  splat: bool,
+  query: Vec<(String, Segment)>,
+  to: String,
+  status: u16,
+  force: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+  Literal(String),
+  Placeholder(String),
+}
+
+impl Segment {
+  fn parse(value: &str) -> Self {
+    match value.strip_prefix(':') {
+      Some(name) if !name.is_empty() => Segment::Placeholder(name.to_string()),
+      _ => Segment::Literal(value.to_string()),
+    }
+  }
+}
+
+/// What to do with a request matched by a rule
+#[derive(Debug, PartialEq)]
+pub enum RuleAction {
+  /// Send the client to "location"
+  Redirect { location: String, status: u16 },
+  /// Serve "uri" instead of the requested path, responding with "status"
+  Rewrite { uri: String, status: u16 },
+}
+
+impl Rule {
+  /// "from" may contain ":name" placeholders matching one path segment and
+  /// end in "*" to match the rest of the path. 3xx statuses redirect,
+  /// anything else rewrites and responds with that status
+  pub fn new(
+    from: &str,
+    to: &str,
+    status: u16,
+    force: bool,
+    query: &[(String, String)],
+  ) -> anyhow::Result<Self> {
+    if !from.starts_with('/') {
+      return Err(anyhow::anyhow!(
+        "Rule path \"{}\" must start with \"/\"",
+        from
+      ));
+    }
+
+    let is_redirect = matches!(status, 301 | 302 | 303 | 307 | 308);
+    let is_rewrite = (200..300).contains(&status) || (400..600).contains(&status);
+    if !is_redirect && !is_rewrite {
+      return Err(anyhow::anyhow!(
+        "Expected a redirect status (301, 302, 303, 307, 308) or a rewrite status (2xx, 4xx, 5xx), got {}",
+        status
+      ));
+    }
+
+    if !is_redirect && !to.starts_with('/') {
+      return Err(anyhow::anyhow!(
+        "Rewrite target \"{}\" must be a path on this server, use --proxy for other servers",
+        to
+      ));
+    }
+
+    let mut segments = split_path(from)
+      .into_iter()
+      .map(Segment::parse)
+      .collect::<Vec<Segment>>();
+
+    let splat = segments.last() == Some(&Segment::Literal("*".to_string()));
+    if splat {
+      segments.pop();
+    }
+
+    Ok(Self {
+      from: segments,
+      splat,
+      query: query
+        .iter()
+        .map(|(key, value)| (key.clone(), Segment::parse(value)))
+        .collect(),
+      to: to.to_string(),
+      status,
+      force,
+    })
+  }
+
+  /// Placeholder values when "path" and "query" satisfy the rule
+  fn matches(
+    &self,
+    path: &str,
+    query: &[(String, String)],
+  ) -> Option<HashMap<String, String>> {
+    let segments = split_path(path);
+    let mut values = HashMap::<String, String>::new();
+
+    if segments.len() < self.from.len() || !self.splat && segments.len() != self.from.len() {
+      return None;
+    }
+
+    for (pattern, segment) in self.from.iter().zip(segments.iter()) {
+      match pattern {
+        Segment::Literal(literal) if literal == segment => {}
+        Segment::Literal(_) => return None,
+        Segment::Placeholder(name) => {
+          values.insert(name.clone(), segment.to_string());
+        }
+      }
+    }
+
+    if self.splat {
+      values.insert("splat".to_string(), segments[self.from.len()..].join("/"));
+    }
+
+    for (key, pattern) in self.query.iter() {
+      let (_, value) = query.iter().find(|(k, _)| k == key)?;
+      match pattern {
+        Segment::Literal(literal) if literal == value => {}
+        Segment::Literal(_) => return None,
+        Segment::Placeholder(name) => {
+          values.insert(name.clone(), value.clone());
+        }
+      }
+    }
+
+    Some(values)
+  }
+
+  /// Substitute ":name" placeholders in the target, unknown names are kept
+  /// as is so ports such as "localhost:3000" survive
+  fn target(
+    &self,
+    values: &HashMap<String, String>,
+  ) -> String {
+    let mut output = String::new();
+    let mut rest = self.to.as_str();
+
+    while let Some(start) = rest.find(':') {
+      output.push_str(&rest[..start]);
+      let after = &rest[start + 1..];
+      let len = after
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(after.len());
+
+      match values.get(&after[..len]) {
+        Some(value) if len > 0 => output.push_str(value),
+        _ => {
+          output.push(':');
+          output.push_str(&after[..len]);
+        }
+      }
+      rest = &after[len..];
+    }
+
+    output.push_str(rest);
+    output
+  }
+}
+
+/// Find the first rule matching a request. Unless forced, rules don't
+/// apply to paths that exist on disk ("exists" is called lazily)
+pub fn resolve(
+  rules: &[Rule],
+  path: &str,
+  query: Option<&str>,
+  exists: impl Fn() -> bool,
+) -> Option<RuleAction> {
+  let query_pairs = parse_query(query);
+  let mut path_exists = None;
+
+  for rule in rules {
+    let Some(values) = rule.matches(path, &query_pairs) else {
+      continue;
+    };
+
+    if !rule.force && *path_exists.get_or_insert_with(&exists) {
+      continue;
+    }
+
+    let mut target = rule.target(&values);
+
+    // The original query string is passed on unless the rule consumed it
+    if let Some(query) = query {
+      if rule.query.is_empty() && !target.contains('?') && !query.is_empty() {
+        target = format!("{}?{}", target, query);
+      }
+    }
+
+    return Some(match rule.status {
+      301 | 302 | 303 | 307 | 308 => RuleAction::Redirect {
+        location: target,
+        status: rule.status,
+      },
+      status => RuleAction::Rewrite {
+        uri: target,
+        status,
+      },
+    });
+  }
+
+  None
+}
+
+/// Parse a Netlify-style "_redirects" file. Each line holds
+/// "from [key=value ...] to [status[!]] [conditions ...]". Rules with
+/// country, language or role conditions can't be reproduced locally and
+/// are skipped
+pub fn parse_redirects_file(contents: &str) -> anyhow::Result<Vec<Rule>> {
+  let mut rules = Vec::new();
+
+  for (i, line) in contents.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    let parse = || -> anyhow::Result<Option<Rule>> {
+      let mut tokens = line.split_whitespace();
+      let Some(from) = tokens.next() else {
+        return Ok(None);
+      };
+
+      let mut query = Vec::<(String, String)>::new();
+      let mut to = None;
+      for token in tokens.by_ref() {
+        match token.split_once('=') {
+          Some((key, value)) if !token.starts_with('/') && !token.contains("://") => {
+            query.push((key.to_string(), value.to_string()));
+          }
+          _ => {
+            to = Some(token);
+            break;
+          }
+        }
+      }
+
+      let Some(to) = to else {
+        return Err(anyhow::anyhow!("Missing redirect target"));
+      };
+
+      let mut status = 301;
+      let mut force = false;
+      for token in tokens {
+        if let Ok(code) = token.trim_end_matches('!').parse::<u16>() {
+          status = code;
+          force = token.ends_with('!');
+        } else {
+          return Ok(None);
+        }
+      }
+
+      Ok(Some(Rule::new(from, to, status, force, &query)?))
+    };
+
+    match parse() {
+      Ok(Some(rule)) => rules.push(rule),
+      Ok(None) => {}
+      Err(error) => {
+        return Err(anyhow::anyhow!("line {}: {}", i + 1, error));
+      }
+    }
+  }
+
+  Ok(rules)
+}
+
+/// Path segments with the leading and trailing slash removed
+fn split_path(path: &str) -> Vec<&str> {
+  let path = path.trim_matches('/');
+  if path.is_empty() {
+    return Vec::new();
+  }
+  path.split('/').collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rule(
+    from: &str,
+    query: &[(&str, &str)],
+  ) -> Rule {
+    let query = query
+      .iter()
+      .map(|(key, value)| (key.to_string(), value.to_string()))
+      .collect::<Vec<(String, String)>>();
+    Rule::new(from, "/to", 200, false, &query).unwrap()
+  }
+
+  fn values(pairs: &[(&str, &str)]) -> Option<HashMap<String, String>> {
+    Some(
+      pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect(),
+    )
+  }
+
+  #[test]
+  fn literal_paths_match_exactly() {
+    let rule = rule("/old/page", &[]);
+    assert_eq!(rule.matches("/old/page", &[]), values(&[]));
+    assert_eq!(rule.matches("/old/page/", &[]), values(&[]));
+    assert_eq!(rule.matches("/old", &[]), None);
+    assert_eq!(rule.matches("/old/page/more", &[]), None);
+    assert_eq!(rule.matches("/old/other", &[]), None);
+  }
+
+  #[test]
+  fn placeholders_match_one_segment() {
+    let rule = rule("/blog/:year/:slug", &[]);
+    assert_eq!(
+      rule.matches("/blog/2024/hello", &[]),
+      values(&[("year", "2024"), ("slug", "hello")])
+    );
+    assert_eq!(rule.matches("/blog/2024", &[]), None);
+    assert_eq!(rule.matches("/blog/2024/hello/extra", &[]), None);
+  }
+
+  #[test]
+  fn splat_matches_the_rest() {
+    let rule = rule("/docs/*", &[]);
+    assert_eq!(
+      rule.matches("/docs/a/b/c", &[]),
+      values(&[("splat", "a/b/c")])
+    );
+    assert_eq!(rule.matches("/docs", &[]), values(&[("splat", "")]));
+    assert_eq!(rule.matches("/documents", &[]), None);
+
+    let root = Rule::new("/*", "/index.html", 200, false, &[]).unwrap();
+    assert_eq!(root.matches("/", &[]), values(&[("splat", "")]));
+    assert_eq!(root.matches("/a/b", &[]), values(&[("splat", "a/b")]));
+  }
+
+  #[test]
+  fn query_conditions() {
+    let rule = rule("/search", &[("q", ":q"), ("lang", "en")]);
+    let query = |pairs: &[(&str, &str)]| {
+      pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<Vec<(String, String)>>()
+    };
+    assert_eq!(
+      rule.matches("/search", &query(&[("q", "rust"), ("lang", "en")])),
+      values(&[("q", "rust")])
+    );
+    assert_eq!(rule.matches("/search", &query(&[("q", "rust")])), None);
+    assert_eq!(
+      rule.matches("/search", &query(&[("q", "rust"), ("lang", "de")])),
+      None
+    );
+  }
+
+  #[test]
+  fn target_substitutes_known_placeholders() {
+    let rule = Rule::new("/u/:id", "http://localhost:3000/users/:id", 302, false, &[]).unwrap();
+    let values = rule.matches("/u/42", &[]).unwrap();
+    assert_eq!(rule.target(&values), "http://localhost:3000/users/42");
+  }
+}
@@ -0,0 +1,142 @@
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_body_util::combinators::BoxBody;
+use http_body_util::Empty;
+use http_body_util::Full;
+use hyper::body::Body;
+use hyper::body::Bytes as HyperBytes;
+use hyper::body::Incoming;
+use hyper::header::HeaderValue;
+use hyper::header::CONTENT_LENGTH;
+use hyper::http::response::Builder as ResponseBuilder;
+use hyper::service::service_fn;
+use hyper::Method;
+use hyper::Request;
+use hyper::Response;
+use hyper_util::rt::TokioExecutor;
+use hyper_util::rt::TokioIo;
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::GracefulShutdown;
+use tokio::net::TcpListener;
+use tokio::net::ToSocketAddrs;
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
+
+pub struct HttpServerOptions<A: ToSocketAddrs> {
+  pub addr: A,
+  pub tls: Option<TlsAcceptor>,
+  /// Stop accepting connections once cancelled and drain in-flight requests
+  pub shutdown: CancellationToken,
+  /// How long to wait for in-flight requests before giving up
+  pub shutdown_timeout: Duration,
+}
+
+/// Simple wrapper around hyper to make it a little nicer to use.
+/// Connections are wrapped in TLS when an acceptor is supplied and
+/// speak either HTTP/1.1 or HTTP/2 (negotiated via ALPN or prior knowledge)
+pub async fn http_server<F, Fut, A>(
+  options: HttpServerOptions<A>,
+  handle_func: F,
+) -> anyhow::Result<()>
+where
+  A: ToSocketAddrs,
+  F: 'static + Send + Sync + Fn(Request<Incoming>, ResponseBuilder) -> Fut,
+  Fut: 'static + Send + Future<Output = anyhow::Result<Response<BoxBody<HyperBytes, Infallible>>>>,
+{
+  let listener = TcpListener::bind(&options.addr).await?;
+  let handler_func_ref = Arc::new(handle_func);
+  let graceful = GracefulShutdown::new();
+
+  loop {
+    let stream = tokio::select! {
+      accepted = listener.accept() => {
+        let Ok((stream, _)) = accepted else {
+          continue;
+        };
+        stream
+      },
+      _ = options.shutdown.cancelled() => break,
+    };
+
+    let handler_func_ref = handler_func_ref.clone();
+    let tls = options.tls.clone();
+    let watcher = graceful.watcher();
+
+    tokio::task::spawn(async move {
+      let service_builder = auto::Builder::new(TokioExecutor::new());
+      let service_handler = service_fn(move |req: Request<Incoming>| {
+        let is_head = req.method() == Method::HEAD;
+        let fut = handler_func_ref(req, Response::builder());
+
+        async move {
+          let mut handler_response = match fut.await {
+            Ok(handler_response) => handler_response,
+            Err(handler_error) => handle_error(handler_error),
+          };
+
+          if is_head {
+            handler_response = without_body(handler_response);
+          }
+
+          Ok::<Response<BoxBody<HyperBytes, Infallible>>, anyhow::Error>(handler_response)
+        }
+      });
+
+      match tls {
+        Some(acceptor) => {
+          let Ok(stream) = acceptor.accept(stream).await else {
+            return;
+          };
+          let conn =
+            service_builder.serve_connection_with_upgrades(TokioIo::new(stream), service_handler);
+          watcher.watch(conn).await.ok();
+        }
+        None => {
+          let conn =
+            service_builder.serve_connection_with_upgrades(TokioIo::new(stream), service_handler);
+          watcher.watch(conn).await.ok();
+        }
+      }
+    });
+  }
+
+  drop(listener);
+
+  tokio::select! {
+    _ = graceful.shutdown() => Ok(()),
+    _ = tokio::time::sleep(options.shutdown_timeout) => Err(anyhow::anyhow!(
+      "Timed out after {:?} waiting for connections to close",
+      options.shutdown_timeout
+    )),
+  }
+}
+
+/// HEAD responses carry the headers of the equivalent GET without the body
+fn without_body(
+  response: Response<BoxBody<HyperBytes, Infallible>>
+) -> Response<BoxBody<HyperBytes, Infallible>> {
+  let (mut parts, body) = response.into_parts();
+
+  if let Some(content_length) = body.size_hint().exact() {
+    if !parts.headers.contains_key(CONTENT_LENGTH) {
+      parts
+        .headers
+        .insert(CONTENT_LENGTH, HeaderValue::from(content_length));
+    }
+  }
+
+  Response::from_parts(parts, BoxBody::new(Empty::new()))
+}
+
+fn handle_error(error: anyhow::Error) -> Response<BoxBody<HyperBytes, Infallible>> {
+  let content = HyperBytes::from(format!("{}", error));
+  let body = BoxBody::new(Full::new(content));
+  let response = Response::builder().status(500).body(body);
+
+  let Ok(response) = response else { todo!() };
+
+  response
+}
@@ -0,0 +1,23 @@
+/// Resolves when the process receives SIGINT or SIGTERM (Ctrl+C on Windows)
+pub async fn shutdown_signal() {
+  #[cfg(unix)]
+  {
+    use tokio::signal::unix::signal;
+    use tokio::signal::unix::SignalKind;
+
+    let Ok(mut sigterm) = signal(SignalKind::terminate()) else {
+      tokio::signal::ctrl_c().await.ok();
+      return;
+    };
+
+    tokio::select! {
+      _ = tokio::signal::ctrl_c() => {},
+      _ = sigterm.recv() => {},
+    }
+  }
+
+  #[cfg(windows)]
+  {
+    tokio::signal::ctrl_c().await.ok();
+  }
+}
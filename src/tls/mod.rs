@@ -0,0 +1,58 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use tokio_rustls::rustls::pki_types::PrivateKeyDer;
+use tokio_rustls::rustls::pki_types::PrivatePkcs8KeyDer;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+/// Build a TLS acceptor from PEM encoded certificate chain and private key files
+pub fn from_pem_files(
+  cert_path: &Path,
+  key_path: &Path,
+) -> anyhow::Result<TlsAcceptor> {
+  let Ok(certs) = CertificateDer::pem_file_iter(cert_path) else {
+    return Err(anyhow::anyhow!(
+      "Unable to read TLS certificate {:?}",
+      cert_path
+    ));
+  };
+  let certs = certs.collect::<Result<Vec<_>, _>>()?;
+
+  if certs.is_empty() {
+    return Err(anyhow::anyhow!("No certificates found in {:?}", cert_path));
+  }
+
+  let Ok(key) = PrivateKeyDer::from_pem_file(key_path) else {
+    return Err(anyhow::anyhow!("Unable to read TLS key {:?}", key_path));
+  };
+
+  build_acceptor(certs, key)
+}
+
+/// Generate a throwaway self-signed certificate for the supplied host names
+pub fn self_signed(hosts: Vec<String>) -> anyhow::Result<TlsAcceptor> {
+  let certified = rcgen::generate_simple_self_signed(hosts)?;
+  let cert = certified.cert.der().clone();
+  let key = PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der());
+
+  build_acceptor(vec![cert], key.into())
+}
+
+fn build_acceptor(
+  certs: Vec<CertificateDer<'static>>,
+  key: PrivateKeyDer<'static>,
+) -> anyhow::Result<TlsAcceptor> {
+  let mut server_config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+    .with_safe_default_protocol_versions()?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)?;
+
+  // Prefer HTTP/2 when the client supports it
+  server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+  Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
@@ -1 +1,3 @@
 pub mod broadcast;
+pub mod query;
+pub mod size;
@@ -0,0 +1,20 @@
+/// Decoded key/value pairs of a query string, in order
+pub fn parse_query(query: Option<&str>) -> Vec<(String, String)> {
+  let Some(query) = query else {
+    return Vec::new();
+  };
+
+  query
+    .split('&')
+    .filter(|pair| !pair.is_empty())
+    .map(|pair| {
+      let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+      let decode = |v: &str| {
+        urlencoding::decode(&v.replace('+', " "))
+          .map(|v| v.to_string())
+          .unwrap_or_else(|_| v.to_string())
+      };
+      (decode(key), decode(value))
+    })
+    .collect()
+}
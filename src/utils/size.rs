@@ -0,0 +1,92 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// Unit system used when printing file sizes
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SizeUnits {
+  /// Powers of 1000 (kB, MB, GB)
+  #[default]
+  Si,
+  /// Powers of 1024 (KiB, MiB, GiB)
+  Iec,
+}
+
+/// Human readable size, e.g. "1.5 MB" or "1.4 MiB"
+pub fn format_size(
+  bytes: u64,
+  units: SizeUnits,
+) -> String {
+  let (base, suffixes) = match units {
+    SizeUnits::Si => (1000.0, ["B", "kB", "MB", "GB", "TB", "PB"]),
+    SizeUnits::Iec => (1024.0, ["B", "KiB", "MiB", "GiB", "TiB", "PiB"]),
+  };
+
+  let mut size = bytes as f64;
+  let mut unit = 0;
+  while size >= base && unit < suffixes.len() - 1 {
+    size /= base;
+    unit += 1;
+  }
+
+  // 999,960 bytes would otherwise print as "1000.0 kB"
+  if unit > 0 && unit < suffixes.len() - 1 && (size * 10.0).round() / 10.0 >= base {
+    size /= base;
+    unit += 1;
+  }
+
+  match unit {
+    0 => format!("{} {}", bytes, suffixes[0]),
+    unit => format!("{:.1} {}", size, suffixes[unit]),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn bytes_are_whole_numbers() {
+    assert_eq!(format_size(0, SizeUnits::Si), "0 B");
+    assert_eq!(format_size(999, SizeUnits::Si), "999 B");
+    assert_eq!(format_size(1023, SizeUnits::Iec), "1023 B");
+  }
+
+  #[test]
+  fn si_units_step_at_1000() {
+    assert_eq!(format_size(1000, SizeUnits::Si), "1.0 kB");
+    assert_eq!(format_size(1024, SizeUnits::Si), "1.0 kB");
+    assert_eq!(format_size(1500, SizeUnits::Si), "1.5 kB");
+    assert_eq!(format_size(1_000_000, SizeUnits::Si), "1.0 MB");
+  }
+
+  #[test]
+  fn iec_units_step_at_1024() {
+    assert_eq!(format_size(1000, SizeUnits::Iec), "1000 B");
+    assert_eq!(format_size(1024, SizeUnits::Iec), "1.0 KiB");
+    assert_eq!(format_size(1536, SizeUnits::Iec), "1.5 KiB");
+    assert_eq!(format_size(1024 * 1024, SizeUnits::Iec), "1.0 MiB");
+  }
+
+  #[test]
+  fn gigabytes() {
+    assert_eq!(format_size(1_500_000_000, SizeUnits::Si), "1.5 GB");
+    assert_eq!(format_size(1_500_000_000, SizeUnits::Iec), "1.4 GiB");
+    assert_eq!(
+      format_size(5 * 1024 * 1024 * 1024, SizeUnits::Iec),
+      "5.0 GiB"
+    );
+  }
+
+  #[test]
+  fn rounding_moves_to_the_next_unit() {
+    assert_eq!(format_size(999_960, SizeUnits::Si), "1.0 MB");
+    assert_eq!(format_size(999_940, SizeUnits::Si), "999.9 kB");
+    assert_eq!(format_size(1024 * 1024 - 1, SizeUnits::Iec), "1.0 MiB");
+  }
+
+  #[test]
+  fn largest_unit_keeps_growing() {
+    assert_eq!(format_size(u64::MAX, SizeUnits::Si), "18446.7 PB");
+  }
+}